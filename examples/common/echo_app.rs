@@ -1,8 +1,9 @@
 use std::sync::Arc;
 
 use satori::{
-    api::SatoriApi,
-    structs::{BotId, ChannelType, Event},
+    error::SessionError,
+    session::Session,
+    structs::{ChannelType, Event},
     Satori, SatoriApp,
 };
 use tracing::debug;
@@ -10,40 +11,26 @@ use tracing::debug;
 pub struct EchoApp {}
 
 impl SatoriApp for EchoApp {
+    type Error = SessionError;
+
     async fn start<S>(&self, _s: &Arc<S>)
     where
         S: Satori + Send + Sync + 'static,
     {
     }
 
-    async fn handle_event<S>(&self, s: &Arc<S>, event: Event)
+    async fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> Result<(), SessionError>
     where
         S: Satori + Send + Sync + 'static,
     {
-        if let Some(user) = &event.user {
-            if user.id == event.self_id {
-                debug!("self event");
-                return;
-            }
-        }
-        if let Some(message) = event.message {
-            if let Some(content) = &message.content {
-                if content.starts_with("echo") {
-                    let bot = BotId {
-                        id: event.self_id,
-                        platform: event.platform,
-                    };
-                    if let Some(ch) = event.channel {
-                        match ch.ty {
-                            Some(ChannelType::Text) => {
-                                let r = s.create_message(&bot, ch.id, content.clone()).await;
-                                debug!("api response:{:?}", r);
-                            }
-                            _ => {}
-                        }
-                    }
-                }
-            }
+        let session = Session::new(s, event);
+        let text = session
+            .channel()
+            .is_ok_and(|c| matches!(c.ty, Some(ChannelType::Text)));
+        if text && session.content().starts_with("echo") {
+            let r = session.send(session.content()).await?;
+            debug!("api response:{:?}", r);
         }
+        Ok(())
     }
 }
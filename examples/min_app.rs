@@ -1,5 +1,7 @@
 use satori::{
     impls::net::sdk::{NetSDK, NetSDKConfig},
+    middleware::{IgnoreSelf, Middlewares},
+    runtime::SatoriOptions,
     satori,
 };
 
@@ -18,11 +20,15 @@ satori! {
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let app = MinApp::new(
+    let app = MinApp::with_options(
         NetSDK::new(NetSDKConfig {
             ..Default::default()
         }),
         EchoApp {},
+        SatoriOptions {
+            middlewares: Middlewares::new().event(IgnoreSelf),
+            ..Default::default()
+        },
     );
     app.start_with_graceful_shutdown(tokio::signal::ctrl_c())
         .await;
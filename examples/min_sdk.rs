@@ -29,7 +29,9 @@ impl SatoriSDK for Echo {
         S: Satori + Send + Sync + 'static,
     {
         if payload.method == "stop" {
-            s.shutdown().await;
+            // shutdown waits for in-flight calls, including this one
+            let s = s.clone();
+            tokio::spawn(async move { s.shutdown().await });
         }
         Err(ApiError::ServerError(500).into())
     }
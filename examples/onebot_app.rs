@@ -1,5 +1,7 @@
 use satori::{
     impls::onebot11::{Onebot11SDK, Onebot11SDKConfig},
+    middleware::{IgnoreSelf, Middlewares},
+    runtime::SatoriOptions,
     satori,
 };
 
@@ -17,7 +19,7 @@ satori! {
 async fn main() {
     tracing_subscriber::fmt::init();
 
-    let app = OnebotApp::new(
+    let app = OnebotApp::with_options(
         Onebot11SDK::new(Onebot11SDKConfig {
             host: todo!(),
             port: todo!(),
@@ -25,6 +27,10 @@ async fn main() {
             self_id: todo!(),
         }),
         (EchoApp {}, EchoApp {}),
+        SatoriOptions {
+            middlewares: Middlewares::new().event(IgnoreSelf),
+            ..Default::default()
+        },
     );
     app.start_with_graceful_shutdown(tokio::signal::ctrl_c())
         .await;
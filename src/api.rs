@@ -9,19 +9,42 @@ use crate::{
     Satori,
 };
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[cfg_attr(test, derive(PartialEq))]
 pub struct RawApiCall {
     pub method: String,
     pub body: Value,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Prefix of the method of internal calls while they pass API middlewares,
+/// e.g. `internal/get_group_info` for [`Satori::call_internal`].
+pub const INTERNAL_PREFIX: &str = "internal/";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[cfg_attr(test, derive(PartialEq))]
 #[serde(tag = "method", content = "body")]
 pub enum TypedApiCall {
     #[serde(rename = "message.create")]
     MessageCreate { channel_id: String, content: String },
+    #[serde(rename = "message.delete")]
+    MessageDelete {
+        channel_id: String,
+        message_id: String,
+    },
+    #[serde(rename = "reaction.create")]
+    ReactionCreate {
+        channel_id: String,
+        message_id: String,
+        emoji: String,
+    },
+    #[serde(rename = "guild.member.get")]
+    GuildMemberGet { guild_id: String, user_id: String },
+    #[serde(rename = "user.get")]
+    UserGet { user_id: String },
+    #[serde(rename = "guild.get")]
+    GuildGet { guild_id: String },
+    #[serde(rename = "channel.get")]
+    ChannelGet { channel_id: String },
 }
 
 pub trait IntoRawApiCall {
@@ -65,6 +88,46 @@ pub trait SatoriApi: Satori + sealed::Sealed {
         channel_id: String,
         content: String,
     ) -> impl Future<Output = Result<Value, SatoriError>> + Send;
+
+    fn delete_message(
+        self: &Arc<Self>,
+        bot: &BotId,
+        channel_id: String,
+        message_id: String,
+    ) -> impl Future<Output = Result<Value, SatoriError>> + Send;
+
+    fn create_reaction(
+        self: &Arc<Self>,
+        bot: &BotId,
+        channel_id: String,
+        message_id: String,
+        emoji: String,
+    ) -> impl Future<Output = Result<Value, SatoriError>> + Send;
+
+    fn get_guild_member(
+        self: &Arc<Self>,
+        bot: &BotId,
+        guild_id: String,
+        user_id: String,
+    ) -> impl Future<Output = Result<GuildMember, SatoriError>> + Send;
+
+    fn get_user(
+        self: &Arc<Self>,
+        bot: &BotId,
+        user_id: String,
+    ) -> impl Future<Output = Result<User, SatoriError>> + Send;
+
+    fn get_guild(
+        self: &Arc<Self>,
+        bot: &BotId,
+        guild_id: String,
+    ) -> impl Future<Output = Result<Guild, SatoriError>> + Send;
+
+    fn get_channel(
+        self: &Arc<Self>,
+        bot: &BotId,
+        channel_id: String,
+    ) -> impl Future<Output = Result<Channel, SatoriError>> + Send;
 }
 
 impl<S> SatoriApi for S
@@ -79,7 +142,7 @@ where
     where
         R: DeserializeOwned,
     {
-        Ok(serde_json::from_value(self.call_api(bot, payload).await?).map_internal_error()?)
+        serde_json::from_value(self.call_api(bot, payload).await?).map_internal_error()
     }
 
     async fn create_message(
@@ -97,6 +160,73 @@ where
         )
         .await
     }
+
+    async fn delete_message(
+        self: &Arc<Self>,
+        bot: &BotId,
+        channel_id: String,
+        message_id: String,
+    ) -> Result<Value, SatoriError> {
+        self.call_api_typed(
+            bot,
+            TypedApiCall::MessageDelete {
+                channel_id,
+                message_id,
+            },
+        )
+        .await
+    }
+
+    async fn create_reaction(
+        self: &Arc<Self>,
+        bot: &BotId,
+        channel_id: String,
+        message_id: String,
+        emoji: String,
+    ) -> Result<Value, SatoriError> {
+        self.call_api_typed(
+            bot,
+            TypedApiCall::ReactionCreate {
+                channel_id,
+                message_id,
+                emoji,
+            },
+        )
+        .await
+    }
+
+    async fn get_guild_member(
+        self: &Arc<Self>,
+        bot: &BotId,
+        guild_id: String,
+        user_id: String,
+    ) -> Result<GuildMember, SatoriError> {
+        self.call_api_typed(bot, TypedApiCall::GuildMemberGet { guild_id, user_id })
+            .await
+    }
+
+    async fn get_user(self: &Arc<Self>, bot: &BotId, user_id: String) -> Result<User, SatoriError> {
+        self.call_api_typed(bot, TypedApiCall::UserGet { user_id })
+            .await
+    }
+
+    async fn get_guild(
+        self: &Arc<Self>,
+        bot: &BotId,
+        guild_id: String,
+    ) -> Result<Guild, SatoriError> {
+        self.call_api_typed(bot, TypedApiCall::GuildGet { guild_id })
+            .await
+    }
+
+    async fn get_channel(
+        self: &Arc<Self>,
+        bot: &BotId,
+        channel_id: String,
+    ) -> Result<Channel, SatoriError> {
+        self.call_api_typed(bot, TypedApiCall::ChannelGet { channel_id })
+            .await
+    }
 }
 
 mod sealed {
@@ -0,0 +1,384 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+use crate::{
+    api::{RawApiCall, SatoriApi},
+    error::{ApiError, SatoriError},
+    middleware::{ApiMiddleware, ApiNext},
+    structs::{BotId, Event},
+    Satori, SATORI,
+};
+
+/// Who issued an API call.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Caller {
+    /// An app or SDK of this process.
+    #[default]
+    Internal,
+    /// A client of [`NetApp`](crate::impls::net::app::NetApp), with the name
+    /// of its token if it has one.
+    Remote { token: Option<String> },
+}
+
+tokio::task_local! {
+    static CALLER: Caller;
+}
+
+impl Caller {
+    /// The caller of the API call being handled by the current task.
+    pub fn current() -> Self {
+        CALLER.try_with(Clone::clone).unwrap_or_default()
+    }
+
+    /// Run `f` on behalf of this caller.
+    pub async fn scope<F>(self, f: F) -> F::Output
+    where
+        F: Future,
+    {
+        CALLER.scope(self, f).await
+    }
+}
+
+/// Whether `method` matches `pattern`: `*` matches everything and
+/// `message.*` every method starting with `message.`.
+pub fn method_matches(pattern: &str, method: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => method.starts_with(prefix),
+        None => pattern == method,
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AuthorityConfig {
+    /// Level of users without any other grant.
+    pub default_level: u32,
+    /// Base level in a guild, by `platform:guild_id`; replaces
+    /// `default_level` there.
+    pub guilds: HashMap<String, u32>,
+    /// Levels by `platform:role_id`; users get the highest of their roles.
+    pub roles: HashMap<String, u32>,
+    /// Levels by `platform:user_id`; overrides everything else.
+    pub users: HashMap<String, u32>,
+    /// Level of remote API callers, by token name (`""` without a token).
+    pub remote: HashMap<String, u32>,
+    /// Level needed to call API methods, by method pattern. The longest
+    /// matching pattern wins; unlisted methods need none. Internal APIs are
+    /// named `internal/<method>`.
+    pub methods: HashMap<String, u32>,
+    /// How long fetched guild roles are kept, in seconds.
+    pub cache_ttl_secs: u64,
+}
+
+impl Default for AuthorityConfig {
+    fn default() -> Self {
+        Self {
+            default_level: 1,
+            guilds: Default::default(),
+            roles: Default::default(),
+            users: Default::default(),
+            remote: Default::default(),
+            methods: Default::default(),
+            cache_ttl_secs: 300,
+        }
+    }
+}
+
+type RoleCache = HashMap<(BotId, String, String), (Instant, Vec<String>)>;
+
+/// Resolves permission levels of users and API callers.
+///
+/// Install it with [`Middlewares::api`](crate::middleware::Middlewares::api)
+/// to deny remote API calls below the level configured in
+/// [`methods`](AuthorityConfig::methods).
+#[derive(Debug, Clone, Default)]
+pub struct Authority {
+    config: Arc<AuthorityConfig>,
+    cache: Arc<Mutex<RoleCache>>,
+}
+
+impl Authority {
+    pub fn new(config: AuthorityConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            ..Default::default()
+        }
+    }
+
+    /// Roles of the event's user, from the event or else the API.
+    async fn roles<S>(&self, s: &Arc<S>, event: &Event) -> Vec<String>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let message = event.message.as_ref();
+        let member = event
+            .member
+            .as_ref()
+            .or(message.and_then(|m| m.member.as_ref()));
+        if let Some(roles) = member.and_then(|m| m.roles.clone()) {
+            return roles;
+        }
+        let guild = event
+            .guild
+            .as_ref()
+            .or(message.and_then(|m| m.guild.as_ref()));
+        let user = event
+            .user
+            .as_ref()
+            .or(message.and_then(|m| m.user.as_ref()));
+        let (Some(guild), Some(user)) = (guild, user) else {
+            return vec![];
+        };
+        let bot = BotId {
+            id: event.self_id.clone(),
+            platform: event.platform.clone(),
+        };
+        let key = (bot, guild.id.clone(), user.id.clone());
+        let ttl = Duration::from_secs(self.config.cache_ttl_secs);
+        if let Some((at, roles)) = self.cache.lock().unwrap().get(&key) {
+            if at.elapsed() < ttl {
+                return roles.clone();
+            }
+        }
+        let roles = match s
+            .get_guild_member(&key.0, key.1.clone(), key.2.clone())
+            .await
+        {
+            Ok(member) => member.roles.unwrap_or_default(),
+            Err(e) => {
+                warn!(target: SATORI, guild = key.1, user = key.2, "failed to fetch roles: {e}");
+                return vec![];
+            }
+        };
+        let mut cache = self.cache.lock().unwrap();
+        cache.retain(|_, (at, _)| at.elapsed() < ttl);
+        cache.insert(key, (Instant::now(), roles.clone()));
+        roles
+    }
+
+    /// The level of the user who caused `event`.
+    pub async fn level<S>(&self, s: &Arc<S>, event: &Event) -> u32
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let config = &self.config;
+        let scoped = |id: &str| format!("{}:{id}", event.platform);
+        let message = event.message.as_ref();
+        let user = event
+            .user
+            .as_ref()
+            .or(message.and_then(|m| m.user.as_ref()));
+        if let Some(level) = user.and_then(|u| config.users.get(&scoped(&u.id))) {
+            return *level;
+        }
+        let guild = event
+            .guild
+            .as_ref()
+            .or(message.and_then(|m| m.guild.as_ref()));
+        let base = guild
+            .and_then(|g| config.guilds.get(&scoped(&g.id)))
+            .unwrap_or(&config.default_level);
+        if config.roles.is_empty() {
+            return *base;
+        }
+        let roles = self.roles(s, event).await;
+        roles
+            .iter()
+            .filter_map(|r| config.roles.get(&scoped(r)))
+            .fold(*base, |level, role| level.max(*role))
+    }
+
+    /// Whether the user who caused `event` has at least `level`.
+    pub async fn check<S>(&self, s: &Arc<S>, event: &Event, level: u32) -> bool
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        self.level(s, event).await >= level
+    }
+
+    pub fn caller_level(&self, caller: &Caller) -> u32 {
+        match caller {
+            Caller::Internal => u32::MAX,
+            Caller::Remote { token } => {
+                let name = token.as_deref().unwrap_or_default();
+                self.config.remote.get(name).copied().unwrap_or(0)
+            }
+        }
+    }
+
+    /// The level needed to call `method`.
+    pub fn method_level(&self, method: &str) -> u32 {
+        self.config
+            .methods
+            .iter()
+            .filter(|(pattern, _)| method_matches(pattern, method))
+            .max_by_key(|(pattern, _)| pattern.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(0)
+    }
+}
+
+impl ApiMiddleware for Authority {
+    async fn call_api(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        next: ApiNext<'_>,
+    ) -> Result<Value, SatoriError> {
+        let caller = Caller::current();
+        let (level, required) = (
+            self.caller_level(&caller),
+            self.method_level(&payload.method),
+        );
+        if level < required {
+            debug!(target: SATORI, ?caller, method = payload.method, level, required, "api call denied");
+            return Err(ApiError::Forbidden.into());
+        }
+        next.run(bot, payload).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+
+    use super::{Authority, AuthorityConfig, Caller};
+    use crate::{
+        api::RawApiCall,
+        dynamic::{BoxFuture, DynSatori},
+        error::{ApiError, SatoriError},
+        middleware::Middlewares,
+        runtime::SatoriOptions,
+        structs::{BotId, Event, Guild, GuildMember, User},
+        testing::{bot, Sink},
+        upload::Upload,
+        Satori,
+    };
+
+    #[tokio::test]
+    async fn test_levels() {
+        let authority = Authority::new(AuthorityConfig {
+            guilds: HashMap::from([("test:muted".to_string(), 0)]),
+            roles: HashMap::from([("test:mod".to_string(), 3)]),
+            users: HashMap::from([("test:owner".to_string(), 5)]),
+            remote: HashMap::from([("bot".to_string(), 2)]),
+            methods: HashMap::from([
+                ("guild.*".to_string(), 3),
+                ("guild.member.kick".to_string(), 4),
+            ]),
+            ..Default::default()
+        });
+        let s = DynSatori::builder().build();
+        let event = |user: &str, guild: &str, roles: &[&str]| Event {
+            platform: "test".to_string(),
+            user: Some(User {
+                id: user.to_string(),
+                ..Default::default()
+            }),
+            guild: Some(Guild {
+                id: guild.to_string(),
+                ..Default::default()
+            }),
+            member: Some(GuildMember {
+                roles: Some(roles.iter().map(ToString::to_string).collect()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert_eq!(authority.level(&s, &event("a", "g", &[])).await, 1);
+        assert_eq!(authority.level(&s, &event("a", "muted", &[])).await, 0);
+        assert_eq!(authority.level(&s, &event("a", "g", &["mod"])).await, 3);
+        assert_eq!(authority.level(&s, &event("owner", "g", &[])).await, 5);
+
+        assert_eq!(authority.method_level("guild.member.kick"), 4);
+        assert_eq!(authority.method_level("guild.get"), 3);
+        assert_eq!(authority.method_level("message.create"), 0);
+
+        let m = Middlewares::new().api(authority);
+        let endpoint = |_: BotId, _: RawApiCall| -> BoxFuture<'static, _> {
+            Box::pin(async { Ok(json!(null)) })
+        };
+        let bot = BotId {
+            id: "1".to_string(),
+            platform: "test".to_string(),
+        };
+        let call = |method: &str| RawApiCall {
+            method: method.to_string(),
+            body: json!({}),
+        };
+        let remote = Caller::Remote {
+            token: Some("bot".to_string()),
+        };
+        assert!(remote
+            .clone()
+            .scope(m.call_api(&bot, call("message.create"), &endpoint))
+            .await
+            .is_ok());
+        assert!(matches!(
+            remote
+                .scope(m.call_api(&bot, call("guild.get"), &endpoint))
+                .await,
+            Err(SatoriError::ApiError(ApiError::Forbidden))
+        ));
+        assert!(m
+            .call_api(&bot, call("guild.member.kick"), &endpoint)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_internal_and_upload() {
+        let authority = Authority::new(AuthorityConfig {
+            methods: HashMap::from([
+                ("internal/*".to_string(), 1),
+                ("upload.create".to_string(), 1),
+            ]),
+            ..Default::default()
+        });
+        let s = DynSatori::builder()
+            .sdk(Sink(Default::default()))
+            .options(SatoriOptions {
+                middlewares: Middlewares::new().api(authority),
+                ..Default::default()
+            })
+            .build();
+        let bot = bot();
+        let call = || {
+            s.call_internal(
+                &bot,
+                RawApiCall {
+                    method: "group/info".to_string(),
+                    body: json!({}),
+                },
+            )
+        };
+        let remote = Caller::Remote { token: None };
+        assert!(matches!(
+            remote.clone().scope(call()).await,
+            Err(SatoriError::ApiError(ApiError::Forbidden))
+        ));
+        // reaches the SDK, which has no internal APIs
+        assert!(matches!(
+            call().await,
+            Err(SatoriError::ApiError(ApiError::NotFound))
+        ));
+
+        let upload = || s.upload(&bot, Upload::new("file"));
+        assert!(matches!(
+            remote.scope(upload()).await,
+            Err(SatoriError::ApiError(ApiError::Forbidden))
+        ));
+        assert!(upload().await.unwrap().starts_with("internal:"));
+    }
+}
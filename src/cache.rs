@@ -0,0 +1,354 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::trace;
+
+use crate::{
+    api::RawApiCall,
+    error::SatoriError,
+    middleware::{ApiMiddleware, ApiNext, EventMiddleware, EventNext},
+    structs::{BotId, Event},
+    SATORI,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// How long entries are kept, in seconds.
+    pub ttl_secs: u64,
+    /// Entries kept at most; the oldest are dropped first.
+    pub capacity: usize,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            ttl_secs: 300,
+            capacity: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    User(BotId, String),
+    Guild(BotId, String),
+    Channel(BotId, String),
+    Member(BotId, String, String),
+}
+
+impl Key {
+    /// The entry answering `payload`, for cached methods.
+    fn of_call(bot: &BotId, payload: &RawApiCall) -> Option<Self> {
+        let field = |name: &str| payload.body.get(name)?.as_str().map(ToString::to_string);
+        let bot = bot.clone();
+        match payload.method.as_str() {
+            "user.get" => Some(Self::User(bot, field("user_id")?)),
+            "guild.get" => Some(Self::Guild(bot, field("guild_id")?)),
+            "channel.get" => Some(Self::Channel(bot, field("channel_id")?)),
+            "guild.member.get" => Some(Self::Member(bot, field("guild_id")?, field("user_id")?)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Entries {
+    values: HashMap<Key, (Instant, Value)>,
+    /// Keys by insertion, oldest first. Keys since removed or stored again
+    /// stay until they reach the front.
+    order: VecDeque<(Instant, Key)>,
+}
+
+impl Entries {
+    /// Whether `key` was stored at `at` and not since.
+    fn is_current(&self, at: Instant, key: &Key) -> bool {
+        self.values.get(key).is_some_and(|(t, _)| *t == at)
+    }
+
+    /// Drop the oldest entry, returning whether there was one.
+    fn pop_oldest(&mut self) -> bool {
+        while let Some((at, key)) = self.order.pop_front() {
+            if self.is_current(at, &key) {
+                self.values.remove(&key);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// A read-through cache for `user.get`, `guild.get`, `channel.get` and
+/// `guild.member.get`.
+///
+/// Install it both with
+/// [`Middlewares::api`](crate::middleware::Middlewares::api) and
+/// [`Middlewares::event`](crate::middleware::Middlewares::event): `*-added`
+/// and `*-updated` events store the object they carry, `*-removed` ones drop
+/// it. Objects merely attached to other events, e.g. a message's author, may
+/// be partial and aren't stored.
+#[derive(Debug, Clone, Default)]
+pub struct Cache {
+    config: Arc<CacheConfig>,
+    entries: Arc<Mutex<Entries>>,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+            ..Default::default()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.values.clear();
+        entries.order.clear();
+    }
+
+    fn get(&self, key: &Key) -> Option<Value> {
+        let ttl = Duration::from_secs(self.config.ttl_secs);
+        let mut entries = self.entries.lock().unwrap();
+        match entries.values.get(key) {
+            Some((at, value)) if at.elapsed() < ttl => Some(value.clone()),
+            Some(_) => {
+                entries.values.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put(&self, key: Key, value: Value) {
+        if self.config.capacity == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if !entries.values.contains_key(&key) && entries.values.len() >= self.config.capacity {
+            entries.pop_oldest();
+        }
+        entries.values.insert(key.clone(), (now, value));
+        entries.order.push_back((now, key));
+        // keep outdated keys from piling up when entries are stored again
+        if entries.order.len() > 2 * self.config.capacity {
+            let Entries { values, order } = &mut *entries;
+            order.retain(|(at, key)| values.get(key).is_some_and(|(t, _)| t == at));
+        }
+    }
+
+    /// Store the objects `event` is about if it carries them whole, and drop
+    /// the entries it outdates otherwise.
+    fn update(&self, event: &Event) {
+        let bot = BotId {
+            id: event.self_id.clone(),
+            platform: event.platform.clone(),
+        };
+        let guild = event.guild.as_ref().map(|g| g.id.clone());
+        let user = event
+            .user
+            .as_ref()
+            .or(event.member.as_ref().and_then(|m| m.user.as_ref()))
+            .map(|u| u.id.clone());
+        let channel = event.channel.as_ref().map(|c| c.id.clone());
+        match event.ty.as_str() {
+            "guild-added" | "guild-updated" => {
+                if let (Some(value), Some(guild)) = (&event.guild, guild) {
+                    let value = serde_json::to_value(value).unwrap();
+                    self.put(Key::Guild(bot, guild), value);
+                }
+            }
+            "guild-member-added" | "guild-member-updated" => {
+                let key = match (guild, user) {
+                    (Some(guild), Some(user)) => Key::Member(bot, guild, user),
+                    _ => return,
+                };
+                match &event.member {
+                    Some(member) => self.put(key, serde_json::to_value(member).unwrap()),
+                    None => {
+                        self.entries.lock().unwrap().values.remove(&key);
+                    }
+                }
+            }
+            "channel-added" | "channel-updated" => {
+                if let (Some(value), Some(channel)) = (&event.channel, channel) {
+                    let value = serde_json::to_value(value).unwrap();
+                    self.put(Key::Channel(bot, channel), value);
+                }
+            }
+            "guild-removed" => {
+                self.entries
+                    .lock()
+                    .unwrap()
+                    .values
+                    .retain(|key, _| match key {
+                        Key::Guild(b, g) | Key::Member(b, g, _) => {
+                            *b != bot || Some(g) != guild.as_ref()
+                        }
+                        _ => true,
+                    });
+            }
+            "guild-member-removed" => {
+                if let (Some(guild), Some(user)) = (guild, user) {
+                    let key = Key::Member(bot, guild, user);
+                    self.entries.lock().unwrap().values.remove(&key);
+                }
+            }
+            "channel-removed" => {
+                if let Some(channel) = channel {
+                    let key = Key::Channel(bot, channel);
+                    self.entries.lock().unwrap().values.remove(&key);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl ApiMiddleware for Cache {
+    async fn call_api(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        next: ApiNext<'_>,
+    ) -> Result<Value, SatoriError> {
+        let Some(key) = Key::of_call(bot, &payload) else {
+            return next.run(bot, payload).await;
+        };
+        if let Some(value) = self.get(&key) {
+            trace!(target: SATORI, ?key, "cache hit");
+            return Ok(value);
+        }
+        let value = next.run(bot, payload).await?;
+        self.put(key, value.clone());
+        Ok(value)
+    }
+}
+
+impl EventMiddleware for Cache {
+    async fn handle_event(&self, event: Event, next: EventNext<'_>) {
+        self.update(&event);
+        next.run(event).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicU32, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use serde_json::json;
+
+    use super::{Cache, CacheConfig};
+    use crate::{
+        api::RawApiCall,
+        dynamic::BoxFuture,
+        middleware::Middlewares,
+        structs::{BotId, Event, Guild, GuildMember, User},
+        testing::bot,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_cache() {
+        let cache = Cache::new(CacheConfig {
+            capacity: 3,
+            ..Default::default()
+        });
+        let m = Middlewares::new().api(cache.clone()).event(cache.clone());
+        let calls = Arc::new(AtomicU32::new(0));
+        let endpoint = {
+            let calls = calls.clone();
+            move |_: BotId, payload: RawApiCall| -> BoxFuture<'static, _> {
+                calls.fetch_add(1, Ordering::Relaxed);
+                Box::pin(async move { Ok(json!({ "id": payload.body["user_id"] })) })
+            }
+        };
+        let bot = bot();
+        let get_user = |id: &str| RawApiCall {
+            method: "user.get".to_string(),
+            body: json!({ "user_id": id }),
+        };
+        let get_member = || RawApiCall {
+            method: "guild.member.get".to_string(),
+            body: json!({ "guild_id": "g", "user_id": "a" }),
+        };
+        let called = || calls.load(Ordering::Relaxed);
+
+        m.call_api(&bot, get_user("a"), &endpoint).await.unwrap();
+        m.call_api(&bot, get_user("a"), &endpoint).await.unwrap();
+        assert_eq!(called(), 1);
+        tokio::time::advance(Duration::from_secs(301)).await;
+        m.call_api(&bot, get_user("a"), &endpoint).await.unwrap();
+        assert_eq!(called(), 2);
+
+        let event = |ty: &str| Event {
+            ty: ty.to_string(),
+            platform: "test".to_string(),
+            self_id: "1".to_string(),
+            user: Some(User {
+                id: "a".to_string(),
+                ..Default::default()
+            }),
+            guild: Some(Guild {
+                id: "g".to_string(),
+                ..Default::default()
+            }),
+            member: Some(GuildMember {
+                nick: Some("nick".to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let endpoint_event = |_: Event| -> BoxFuture<'static, ()> { Box::pin(async {}) };
+        m.call_api(&bot, get_member(), &endpoint).await.unwrap();
+        assert_eq!((called(), cache.len()), (3, 2));
+
+        // the partial objects events carry aren't stored
+        m.handle_event(event("message-created"), &endpoint_event)
+            .await;
+        let member = m.call_api(&bot, get_member(), &endpoint).await.unwrap();
+        assert_eq!(member, json!({ "id": "a" }));
+        assert_eq!((called(), cache.len()), (3, 2));
+
+        // but those of an update are
+        m.handle_event(event("guild-member-updated"), &endpoint_event)
+            .await;
+        let member = m.call_api(&bot, get_member(), &endpoint).await.unwrap();
+        assert_eq!(member["nick"], json!("nick"));
+        assert_eq!(called(), 3);
+
+        m.handle_event(event("guild-member-removed"), &endpoint_event)
+            .await;
+        m.call_api(&bot, get_member(), &endpoint).await.unwrap();
+        assert_eq!(called(), 4);
+
+        // full, so the oldest entry makes room
+        m.call_api(&bot, get_user("b"), &endpoint).await.unwrap();
+        m.call_api(&bot, get_user("c"), &endpoint).await.unwrap();
+        assert_eq!((called(), cache.len()), (6, 3));
+        m.call_api(&bot, get_member(), &endpoint).await.unwrap();
+        assert_eq!(called(), 6);
+        m.call_api(&bot, get_user("a"), &endpoint).await.unwrap();
+        assert_eq!(called(), 7);
+    }
+}
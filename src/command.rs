@@ -0,0 +1,889 @@
+use std::{collections::HashMap, fmt::Write, future::Future, ops::Deref, sync::Arc};
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tracing::{debug, warn};
+
+use crate::{
+    authority::Authority,
+    dynamic::{AnySatori, BoxFuture},
+    error::{CommandError, SessionError},
+    session::Session,
+    structs::{Argv, Event},
+    Satori, SatoriApp, SATORI,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgKind {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    /// The rest of the input, verbatim with its quotes, unless it is a single
+    /// quoted string. Must be the last argument.
+    Text,
+}
+
+impl ArgKind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Integer => "integer",
+            Self::Number => "number",
+            Self::Boolean => "boolean",
+            Self::Text => "text",
+        }
+    }
+
+    fn parse(self, name: &str, raw: &str) -> Result<Value, CommandError> {
+        let invalid = || CommandError::InvalidValue {
+            name: name.to_string(),
+            kind: self.name(),
+            value: raw.to_string(),
+        };
+        Ok(match self {
+            Self::String | Self::Text => Value::String(raw.to_string()),
+            Self::Integer => raw.parse::<i64>().map_err(|_| invalid())?.into(),
+            Self::Number => raw.parse::<f64>().map_err(|_| invalid())?.into(),
+            Self::Boolean => match raw.to_ascii_lowercase().as_str() {
+                "true" | "yes" | "on" | "1" => true.into(),
+                "false" | "no" | "off" | "0" => false.into(),
+                _ => return Err(invalid()),
+            },
+        })
+    }
+
+    /// Check a value from an `interaction/command` event, parsing strings.
+    fn coerce(self, name: &str, value: Value) -> Result<Value, CommandError> {
+        match (self, value) {
+            (_, Value::String(raw)) => self.parse(name, &raw),
+            (Self::Integer, v) if v.is_i64() => Ok(v),
+            (Self::Number, v) if v.is_number() => Ok(v),
+            (Self::Boolean, v) if v.is_boolean() => Ok(v),
+            (Self::String | Self::Text, v) if !v.is_null() => Ok(Value::String(v.to_string())),
+            (_, v) => Err(CommandError::InvalidValue {
+                name: name.to_string(),
+                kind: self.name(),
+                value: v.to_string(),
+            }),
+        }
+    }
+}
+
+/// A positional argument or an option of a [`Command`].
+///
+/// Positional arguments are required unless marked [`optional`](Self::optional);
+/// options are always optional, and boolean options are flags that take no
+/// value.
+#[derive(Debug, Clone)]
+pub struct Arg {
+    name: String,
+    kind: ArgKind,
+    short: Option<char>,
+    required: bool,
+    description: Option<String>,
+}
+
+impl Arg {
+    pub fn new(name: impl Into<String>, kind: ArgKind) -> Self {
+        Self {
+            name: name.into(),
+            kind,
+            short: None,
+            required: true,
+            description: None,
+        }
+    }
+
+    pub fn string(name: impl Into<String>) -> Self {
+        Self::new(name, ArgKind::String)
+    }
+
+    pub fn integer(name: impl Into<String>) -> Self {
+        Self::new(name, ArgKind::Integer)
+    }
+
+    pub fn number(name: impl Into<String>) -> Self {
+        Self::new(name, ArgKind::Number)
+    }
+
+    pub fn boolean(name: impl Into<String>) -> Self {
+        Self::new(name, ArgKind::Boolean)
+    }
+
+    pub fn text(name: impl Into<String>) -> Self {
+        Self::new(name, ArgKind::Text)
+    }
+
+    pub fn optional(mut self) -> Self {
+        self.required = false;
+        self
+    }
+
+    pub fn short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    fn usage(&self) -> String {
+        let dots = if self.kind == ArgKind::Text {
+            "..."
+        } else {
+            ""
+        };
+        match self.required {
+            true => format!("<{}{dots}>", self.name),
+            false => format!("[{}{dots}]", self.name),
+        }
+    }
+
+    fn option_usage(&self) -> String {
+        let short = match self.short {
+            Some(c) => format!("-{c}, "),
+            None => String::new(),
+        };
+        match self.kind {
+            ArgKind::Boolean => format!("{short}--{}", self.name),
+            kind => format!("{short}--{} <{}>", self.name, kind.name()),
+        }
+    }
+}
+
+/// Whether `token` is an option (or `--`) rather than a value, which may be a
+/// negative number.
+fn is_option(token: &str) -> bool {
+    token.starts_with('-')
+        && token.len() > 1
+        && !token[1..].starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// Split `input` on whitespace, honoring quotes. Returns each token with the
+/// byte offset where it starts.
+fn tokenize(input: &str) -> Result<Vec<(String, usize)>, CommandError> {
+    let mut tokens = vec![];
+    let mut current: Option<(String, usize)> = None;
+    let mut quote = None;
+    for (i, c) in input.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.get_or_insert((String::new(), i)).0.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                current.get_or_insert((String::new(), i));
+            }
+            (None, c) if c.is_whitespace() => tokens.extend(current.take()),
+            (None, c) => current.get_or_insert((String::new(), i)).0.push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(CommandError::UnclosedQuote);
+    }
+    tokens.extend(current);
+    Ok(tokens)
+}
+
+#[derive(Debug, Default)]
+struct Parsed {
+    args: HashMap<String, Value>,
+    options: HashMap<String, Value>,
+}
+
+type Permission = Arc<dyn Fn(&Event) -> bool + Send + Sync>;
+
+type Handler = Arc<dyn Fn(CommandContext) -> BoxFuture<'static, anyhow::Result<()>> + Send + Sync>;
+
+pub struct Command {
+    name: String,
+    aliases: Vec<String>,
+    description: Option<String>,
+    args: Vec<Arg>,
+    options: Vec<Arg>,
+    subcommands: Vec<Command>,
+    permission: Option<Permission>,
+    level: u32,
+    handler: Option<Handler>,
+}
+
+impl Command {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            aliases: vec![],
+            description: None,
+            args: vec![],
+            options: vec![],
+            subcommands: vec![],
+            permission: None,
+            level: 0,
+            handler: None,
+        }
+    }
+
+    pub fn alias(mut self, alias: impl Into<String>) -> Self {
+        self.aliases.push(alias.into());
+        self
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn arg(mut self, arg: Arg) -> Self {
+        self.args.push(arg);
+        self
+    }
+
+    pub fn option(mut self, option: Arg) -> Self {
+        self.options.push(option);
+        self
+    }
+
+    pub fn subcommand(mut self, command: Command) -> Self {
+        self.subcommands.push(command);
+        self
+    }
+
+    /// Only run the command (and its subcommands) for events passing `check`.
+    pub fn permission<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&Event) -> bool + Send + Sync + 'static,
+    {
+        self.permission = Some(Arc::new(check));
+        self
+    }
+
+    /// Only run the command (and its subcommands) for users with at least
+    /// `level`, see [`Commands::authority`].
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level;
+        self
+    }
+
+    pub fn handler<F, Fut>(mut self, handler: F) -> Self
+    where
+        F: Fn(CommandContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.handler = Some(Arc::new(move |ctx| Box::pin(handler(ctx))));
+        self
+    }
+}
+
+impl Command {
+    fn matches(&self, name: &str) -> bool {
+        self.name == name || self.aliases.iter().any(|a| a == name)
+    }
+
+    fn find(&self, name: &str) -> Option<&Command> {
+        self.subcommands.iter().find(|c| c.matches(name))
+    }
+
+    fn find_option(&self, name: &str) -> Option<&Arg> {
+        match name.strip_prefix("--") {
+            Some(long) => self.options.iter().find(|o| o.name == long),
+            None => {
+                let mut chars = name.strip_prefix('-')?.chars();
+                let (c, None) = (chars.next()?, chars.next()) else {
+                    return None;
+                };
+                self.options.iter().find(|o| o.short == Some(c))
+            }
+        }
+    }
+
+    /// Whether `--help` or `-h` (unless declared as options) is given in
+    /// option position, i.e. not inside a text argument, after `--` or as an
+    /// option value.
+    fn wants_help(&self, input: &str) -> bool {
+        let Ok(tokens) = tokenize(input) else {
+            return false;
+        };
+        let mut positional = self.args.iter();
+        let mut tokens = tokens.into_iter();
+        while let Some((token, _)) = tokens.next() {
+            if token == "--" {
+                return false;
+            }
+            if !is_option(&token) {
+                match positional.next() {
+                    Some(arg) if arg.kind != ArgKind::Text => continue,
+                    _ => return false,
+                }
+            }
+            let name = token
+                .split_once('=')
+                .map_or(token.as_str(), |(name, _)| name);
+            match self.find_option(name) {
+                Some(option) if option.kind != ArgKind::Boolean && name == token => {
+                    tokens.next();
+                }
+                Some(_) => {}
+                None if name == "--help" || name == "-h" => return true,
+                None => {}
+            }
+        }
+        false
+    }
+
+    fn parse(&self, input: &str) -> Result<Parsed, CommandError> {
+        let mut parsed = Parsed::default();
+        let mut positional = self.args.iter();
+        let mut tokens = tokenize(input)?.into_iter();
+        let mut options_done = false;
+        while let Some((token, start)) = tokens.next() {
+            let is_option = !options_done && is_option(&token);
+            if is_option && token == "--" {
+                options_done = true;
+                continue;
+            }
+            if is_option {
+                let (name, inline) = match token.split_once('=') {
+                    Some((name, value)) => (name, Some(value.to_string())),
+                    None => (token.as_str(), None),
+                };
+                let option = self
+                    .find_option(name)
+                    .ok_or_else(|| CommandError::UnknownOption(name.to_string()))?;
+                let value = match (option.kind, inline) {
+                    (_, Some(raw)) => option.kind.parse(&option.name, &raw)?,
+                    (ArgKind::Boolean, None) => true.into(),
+                    (kind, None) => match tokens.next() {
+                        Some((raw, _)) => kind.parse(&option.name, &raw)?,
+                        None => return Err(CommandError::MissingOptionValue(option.name.clone())),
+                    },
+                };
+                parsed.options.insert(option.name.clone(), value);
+                continue;
+            }
+            let arg = positional.next().ok_or(CommandError::TooManyArguments)?;
+            if arg.kind == ArgKind::Text {
+                let rest = input[start..].trim_end();
+                let text = match tokens.next() {
+                    None if rest.starts_with(['"', '\'']) => token,
+                    _ => rest.to_string(),
+                };
+                parsed.args.insert(arg.name.clone(), text.into());
+                break;
+            }
+            parsed
+                .args
+                .insert(arg.name.clone(), arg.kind.parse(&arg.name, &token)?);
+        }
+        match positional.find(|a| a.required && !parsed.args.contains_key(&a.name)) {
+            Some(missing) => Err(CommandError::MissingArgument(missing.name.clone())),
+            None => Ok(parsed),
+        }
+    }
+
+    fn parse_argv(&self, argv: Argv) -> Result<Parsed, CommandError> {
+        let mut parsed = Parsed::default();
+        let mut values = argv.arguments.into_iter();
+        for arg in &self.args {
+            match values.next() {
+                Some(value) => {
+                    let value = arg.kind.coerce(&arg.name, value)?;
+                    parsed.args.insert(arg.name.clone(), value);
+                }
+                None if arg.required => {
+                    return Err(CommandError::MissingArgument(arg.name.clone()))
+                }
+                None => {}
+            }
+        }
+        if values.next().is_some() {
+            return Err(CommandError::TooManyArguments);
+        }
+        for (name, value) in argv.options {
+            let option = self
+                .options
+                .iter()
+                .find(|o| o.name == name)
+                .ok_or_else(|| CommandError::UnknownOption(name.clone()))?;
+            let value = option.kind.coerce(&name, value)?;
+            parsed.options.insert(name, value);
+        }
+        Ok(parsed)
+    }
+
+    fn usage(&self, path: &str) -> String {
+        let mut usage = path.to_string();
+        for arg in &self.args {
+            usage.push(' ');
+            usage.push_str(&arg.usage());
+        }
+        if !self.options.is_empty() {
+            usage.push_str(" [options]");
+        }
+        if !self.subcommands.is_empty() {
+            usage.push_str(" <command>");
+        }
+        usage
+    }
+
+    fn help(&self, path: &str) -> String {
+        let mut help = self.usage(path);
+        if let Some(description) = &self.description {
+            let _ = write!(help, "\n{description}");
+        }
+        if !self.aliases.is_empty() {
+            let _ = write!(help, "\nAliases: {}", self.aliases.join(", "));
+        }
+        if self.args.iter().any(|a| a.description.is_some()) {
+            help.push_str("\n\nArguments:");
+            for arg in &self.args {
+                let _ = write!(help, "\n  {}", arg.usage());
+                if let Some(description) = &arg.description {
+                    let _ = write!(help, "  {description}");
+                }
+            }
+        }
+        if !self.options.is_empty() {
+            help.push_str("\n\nOptions:");
+            for option in &self.options {
+                let _ = write!(help, "\n  {}", option.option_usage());
+                if let Some(description) = &option.description {
+                    let _ = write!(help, "  {description}");
+                }
+            }
+        }
+        if !self.subcommands.is_empty() {
+            help.push_str("\n\nCommands:");
+            for command in &self.subcommands {
+                let _ = write!(help, "\n  {}", command.name);
+                if let Some(description) = &command.description {
+                    let _ = write!(help, "  {description}");
+                }
+            }
+        }
+        help
+    }
+}
+
+/// What a command handler gets to work with.
+///
+/// Dereferences to the [`Session`] of the invoking event.
+pub struct CommandContext {
+    session: Session<AnySatori>,
+    command: String,
+    args: HashMap<String, Value>,
+    options: HashMap<String, Value>,
+}
+
+impl Deref for CommandContext {
+    type Target = Session<AnySatori>;
+
+    fn deref(&self) -> &Session<AnySatori> {
+        &self.session
+    }
+}
+
+impl CommandContext {
+    /// The invoked command path, e.g. `"config set"`.
+    pub fn command(&self) -> &str {
+        &self.command
+    }
+
+    /// A positional argument, `None` if missing or not a `T`.
+    pub fn arg<T>(&self, name: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_value(self.args.get(name)?.clone()).ok()
+    }
+
+    /// An option, `None` if not given or not a `T`.
+    pub fn option<T>(&self, name: &str) -> Option<T>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_value(self.options.get(name)?.clone()).ok()
+    }
+
+    /// Whether a boolean option was given (and not set to false).
+    pub fn flag(&self, name: &str) -> bool {
+        self.option(name).unwrap_or(false)
+    }
+
+    pub fn session(&self) -> &Session<AnySatori> {
+        &self.session
+    }
+}
+
+async fn send(s: &Arc<AnySatori>, event: &Event, content: String) -> Result<(), SessionError> {
+    Session::new(s, event.clone()).send(content).await?;
+    Ok(())
+}
+
+/// An app dispatching chat commands (and `interaction/command` events) to
+/// handlers.
+///
+/// Unless a `help` command is declared, `help [command]` and `--help` reply
+/// with generated help text.
+#[derive(Default)]
+pub struct Commands {
+    prefixes: Vec<String>,
+    commands: Vec<Command>,
+    authority: Option<Authority>,
+}
+
+impl Commands {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only treat messages starting with `prefix` as commands. Without any
+    /// prefix, every message is.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefixes.push(prefix.into());
+        self
+    }
+
+    pub fn command(mut self, command: Command) -> Self {
+        self.commands.push(command);
+        self
+    }
+
+    /// Resolve user levels for [`Command::level`] with `authority`.
+    pub fn authority(mut self, authority: Authority) -> Self {
+        self.authority = Some(authority);
+        self
+    }
+
+    fn find(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|c| c.matches(name))
+    }
+
+    fn strip_prefix<'a>(&self, content: &'a str) -> Option<&'a str> {
+        if self.prefixes.is_empty() {
+            return Some(content);
+        }
+        self.prefixes
+            .iter()
+            .find_map(|p| content.strip_prefix(p.as_str()))
+    }
+
+    fn display_prefix(&self) -> &str {
+        self.prefixes
+            .first()
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+
+    fn help(&self) -> String {
+        let mut help = String::from("Commands:");
+        for command in &self.commands {
+            let _ = write!(help, "\n  {}{}", self.display_prefix(), command.name);
+            if let Some(description) = &command.description {
+                let _ = write!(help, "  {description}");
+            }
+        }
+        help
+    }
+
+    /// Walk down subcommands named in `input`, checking permissions on the
+    /// way. Returns the command, its path and the remaining input, or `None`
+    /// if permission is denied.
+    async fn resolve<'a, 'c>(
+        &'c self,
+        s: &Arc<AnySatori>,
+        event: &Event,
+        root: &'c Command,
+        input: &'a str,
+    ) -> Option<(&'c Command, String, &'a str)> {
+        let mut command = root;
+        let mut path = format!("{}{}", self.display_prefix(), root.name);
+        let mut rest = input;
+        let mut level = 0;
+        loop {
+            if command.permission.as_ref().is_some_and(|p| !p(event)) {
+                return None;
+            }
+            level = level.max(command.level);
+            let trimmed = rest.trim_start();
+            let word = trimmed.split_whitespace().next().unwrap_or_default();
+            match command.find(word) {
+                Some(sub) => {
+                    command = sub;
+                    let _ = write!(path, " {}", sub.name);
+                    rest = &trimmed[word.len()..];
+                }
+                None => break,
+            }
+        }
+        self.has_level(s, event, level)
+            .await
+            .then_some((command, path, rest))
+    }
+
+    async fn has_level(&self, s: &Arc<AnySatori>, event: &Event, level: u32) -> bool {
+        if level == 0 {
+            return true;
+        }
+        match &self.authority {
+            Some(authority) => authority.check(s, event, level).await,
+            None => {
+                warn!(target: SATORI, level, "command requires a level but no authority is set");
+                false
+            }
+        }
+    }
+
+    async fn builtin_help(
+        &self,
+        s: &Arc<AnySatori>,
+        event: &Event,
+        input: &str,
+    ) -> anyhow::Result<()> {
+        let input = input.trim_start();
+        let name = input.split_whitespace().next().unwrap_or_default();
+        let help = match self.find(name) {
+            Some(root) => match self.resolve(s, event, root, &input[name.len()..]).await {
+                Some((command, path, _)) => command.help(&path),
+                None => return Ok(()),
+            },
+            None => self.help(),
+        };
+        send(s, event, help).await?;
+        Ok(())
+    }
+
+    async fn handle_text(
+        &self,
+        s: &Arc<AnySatori>,
+        event: Event,
+        content: &str,
+    ) -> anyhow::Result<()> {
+        let Some(input) = self.strip_prefix(content.trim()) else {
+            return Ok(());
+        };
+        let name = input.split_whitespace().next().unwrap_or_default();
+        let rest = &input.trim_start()[name.len()..];
+        let Some(root) = self.find(name) else {
+            if name == "help" {
+                self.builtin_help(s, &event, rest).await?;
+            }
+            return Ok(());
+        };
+        let Some((command, path, rest)) = self.resolve(s, &event, root, rest).await else {
+            debug!(target: SATORI, command = name, "permission denied");
+            send(s, &event, "Permission denied.".to_string()).await?;
+            return Ok(());
+        };
+        if command.wants_help(rest) || command.handler.is_none() {
+            send(s, &event, command.help(&path)).await?;
+            return Ok(());
+        }
+        match command.parse(rest) {
+            Ok(parsed) => self.run(s, event, command, path, parsed).await,
+            Err(e) => {
+                send(s, &event, format!("{e}\nUsage: {}", command.usage(&path))).await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn handle_argv(&self, s: &Arc<AnySatori>, mut event: Event) -> anyhow::Result<()> {
+        let Some(argv) = event.argv.take() else {
+            return Ok(());
+        };
+        let mut names = argv.name.split(['.', ' ']).filter(|n| !n.is_empty());
+        let Some(mut command) = names.next().and_then(|n| self.find(n)) else {
+            return Ok(());
+        };
+        let mut path = format!("{}{}", self.display_prefix(), command.name);
+        let mut level = 0;
+        loop {
+            if command.permission.as_ref().is_some_and(|p| !p(&event)) {
+                send(s, &event, "Permission denied.".to_string()).await?;
+                return Ok(());
+            }
+            level = level.max(command.level);
+            let Some(name) = names.next() else { break };
+            let Some(sub) = command.find(name) else {
+                return Ok(());
+            };
+            command = sub;
+            let _ = write!(path, " {}", sub.name);
+        }
+        if !self.has_level(s, &event, level).await {
+            send(s, &event, "Permission denied.".to_string()).await?;
+            return Ok(());
+        }
+        if command.handler.is_none() {
+            send(s, &event, command.help(&path)).await?;
+            return Ok(());
+        }
+        match command.parse_argv(argv) {
+            Ok(parsed) => self.run(s, event, command, path, parsed).await,
+            Err(e) => {
+                send(s, &event, format!("{e}\nUsage: {}", command.usage(&path))).await?;
+                Ok(())
+            }
+        }
+    }
+
+    async fn run(
+        &self,
+        s: &Arc<AnySatori>,
+        event: Event,
+        command: &Command,
+        path: String,
+        parsed: Parsed,
+    ) -> anyhow::Result<()> {
+        let Some(handler) = &command.handler else {
+            return Ok(());
+        };
+        debug!(target: SATORI, command = path, "run command");
+        handler(CommandContext {
+            session: Session::new(s, event),
+            command: path,
+            args: parsed.args,
+            options: parsed.options,
+        })
+        .await
+    }
+}
+
+impl SatoriApp for Commands {
+    type Error = anyhow::Error;
+
+    async fn start<T>(&self, _s: &Arc<T>)
+    where
+        T: Satori + Send + Sync + 'static,
+    {
+    }
+
+    async fn handle_event<T>(&self, s: &Arc<T>, event: Event) -> anyhow::Result<()>
+    where
+        T: Satori + Send + Sync + 'static,
+    {
+        let s = &AnySatori::new(s);
+        match event.ty.as_str() {
+            "interaction/command" => self.handle_argv(s, event).await,
+            "message-created" => {
+                let Some(content) = event.message.as_ref().and_then(|m| m.content.clone()) else {
+                    return Ok(());
+                };
+                self.handle_text(s, event, &content).await
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use serde_json::json;
+
+    use super::{Arg, Command, Commands};
+    use crate::{
+        dynamic::DynSatori,
+        error::CommandError,
+        structs::{Argv, Event},
+        testing::{message, Sink},
+        Satori,
+    };
+
+    fn echo() -> Command {
+        Command::new("echo")
+            .alias("say")
+            .arg(Arg::text("text"))
+            .option(Arg::integer("times").short('t'))
+            .option(Arg::boolean("upper"))
+            .handler(|ctx| async move {
+                let text: String = ctx.arg("text").unwrap();
+                let text = match ctx.flag("upper") {
+                    true => text.to_uppercase(),
+                    false => text,
+                };
+                let times = ctx.option("times").unwrap_or(1);
+                ctx.reply(vec![text; times].join(" ")).await?;
+                Ok(())
+            })
+    }
+
+    #[test]
+    fn test_parse() {
+        let command = echo();
+        let parsed = command.parse(r#"-t 2 --upper "a  b" c"#).unwrap();
+        assert_eq!(parsed.options["times"], json!(2));
+        assert_eq!(parsed.options["upper"], json!(true));
+        assert_eq!(parsed.args["text"], json!(r#""a  b" c"#));
+        let parsed = command.parse(r#""a  b""#).unwrap();
+        assert_eq!(parsed.args["text"], json!("a  b"));
+
+        assert!(matches!(
+            command.parse("--times x hi"),
+            Err(CommandError::InvalidValue { .. })
+        ));
+        assert!(matches!(
+            command.parse("--times=1"),
+            Err(CommandError::MissingArgument(_))
+        ));
+        assert!(matches!(
+            command.parse("--loud hi"),
+            Err(CommandError::UnknownOption(_))
+        ));
+        assert!(command.wants_help("-t 2 --help"));
+        assert!(!command.wants_help(r#""use --help""#));
+        assert!(!command.wants_help("use --help"));
+        assert!(!command.wants_help("-- --help"));
+        assert_eq!(
+            command.help("/echo"),
+            "/echo <text...> [options]\nAliases: say\n\nOptions:\n  -t, --times <integer>\n  --upper"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let admin = Command::new("admin")
+            .permission(|e| e.user.as_ref().is_some_and(|u| u.id == "root"))
+            .subcommand(Command::new("stop").handler(|ctx| async move {
+                ctx.reply("stopping").await?;
+                Ok(())
+            }));
+        let s = DynSatori::builder()
+            .sdk(Sink(sent.clone()))
+            .app(Commands::new().prefix("/").command(echo()).command(admin))
+            .build();
+        s.spawn().await;
+
+        let message = |content: &str| message("a", content);
+        let channel = message("").channel;
+        s.handle_event(message("/say -t 2 hi")).await;
+        s.handle_event(message("echo ignored")).await;
+        s.handle_event(message("/admin stop")).await;
+        s.handle_event(Event {
+            ty: "interaction/command".to_string(),
+            channel,
+            argv: Some(Argv {
+                name: "echo".to_string(),
+                arguments: vec![json!("yo")],
+                options: json!({ "upper": true }).as_object().unwrap().clone(),
+            }),
+            ..Default::default()
+        })
+        .await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut sent = sent.lock().unwrap().clone();
+        sent.sort_by_key(|v| v.to_string());
+        assert_eq!(
+            sent,
+            vec![json!("Permission denied."), json!("YO"), json!("hi hi")]
+        );
+        s.shutdown().await;
+    }
+}
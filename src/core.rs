@@ -4,8 +4,11 @@ use serde_json::Value;
 
 use crate::{
     api::{IntoRawApiCall, RawApiCall},
-    error::SatoriError,
+    error::{ApiError, SatoriError},
+    routing::Route,
+    runtime::{LifecycleState, Runtime},
     structs::{BotId, Event, Login},
+    upload::Upload,
 };
 
 pub trait SatoriSDK {
@@ -22,17 +25,59 @@ pub trait SatoriSDK {
     where
         S: Satori + Send + Sync + 'static;
 
+    /// Call a platform-specific API outside the Satori protocol. SDKs
+    /// without any answer [`ApiError::NotFound`].
+    fn call_internal<S>(
+        &self,
+        _s: &Arc<S>,
+        _bot: &BotId,
+        _payload: RawApiCall,
+    ) -> impl Future<Output = Result<Value, SatoriError>> + Send
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        async { Err(ApiError::NotFound.into()) }
+    }
+
+    /// Upload `file` to the platform, returning its URL. SDKs without
+    /// support answer [`ApiError::NotFound`] and the file is kept in the
+    /// [`UploadStore`](crate::upload::UploadStore) instead. SDKs for remote
+    /// servers must not, as the server can't fetch the stored file.
+    fn upload<S>(
+        &self,
+        _s: &Arc<S>,
+        _bot: &BotId,
+        _file: Upload,
+    ) -> impl Future<Output = Result<String, SatoriError>> + Send
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        async { Err(ApiError::NotFound.into()) }
+    }
+
     fn has_bot(&self, bot: &BotId) -> impl Future<Output = bool> + Send;
 
     fn get_logins(&self) -> impl Future<Output = Vec<Login>> + Send;
+
+    /// URL prefixes of platform resources that may be fetched through the
+    /// Satori proxy route.
+    fn proxy_urls(&self) -> Vec<String> {
+        vec![]
+    }
 }
 
 pub trait SatoriApp {
+    type Error: Into<anyhow::Error>;
+
     fn start<S>(&self, s: &Arc<S>) -> impl Future<Output = ()> + Send
     where
         S: Satori + Send + Sync + 'static;
 
-    fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> impl Future<Output = ()> + Send
+    fn handle_event<S>(
+        &self,
+        s: &Arc<S>,
+        event: Event,
+    ) -> impl Future<Output = Result<(), Self::Error>> + Send
     where
         S: Satori + Send + Sync + 'static;
 }
@@ -49,8 +94,25 @@ pub trait Satori {
     ) -> impl Future<Output = Result<Value, SatoriError>> + Send
     where
         T: IntoRawApiCall + Send;
-    fn handle_event(self: &Arc<Self>, event: Event);
+    fn call_internal(
+        self: &Arc<Self>,
+        bot: &BotId,
+        payload: RawApiCall,
+    ) -> impl Future<Output = Result<Value, SatoriError>> + Send;
+    fn upload(
+        self: &Arc<Self>,
+        bot: &BotId,
+        file: Upload,
+    ) -> impl Future<Output = Result<String, SatoriError>> + Send;
+    fn handle_event(self: &Arc<Self>, event: Event) -> impl Future<Output = ()> + Send;
     fn get_logins(self: &Arc<Self>) -> impl Future<Output = Vec<Login>> + Send;
+    fn proxy_urls(self: &Arc<Self>) -> Vec<String>;
+    fn routes(self: &Arc<Self>) -> Vec<Route>;
+    fn runtime(self: &Arc<Self>) -> &Runtime;
+
+    fn state(self: &Arc<Self>) -> LifecycleState {
+        self.runtime().state()
+    }
 
     fn stopped(self: &Arc<Self>) -> impl Future<Output = ()> + Send;
 }
@@ -0,0 +1,147 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    sync::Mutex,
+    time::Duration,
+};
+
+use tokio::time::Instant;
+
+use crate::{structs::Event, system::is_internal};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Id {
+    Message(String),
+    Event(i64),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    platform: String,
+    self_id: String,
+    ty: String,
+    id: Id,
+}
+
+impl Key {
+    /// New messages are identified by their id, which is the same through
+    /// every upstream; other events by their event id, since a message can
+    /// be edited, reacted to or deleted many times.
+    fn of(event: &Event) -> Option<Self> {
+        if is_internal(event) {
+            return None;
+        }
+        let message = event
+            .message
+            .as_ref()
+            .filter(|m| event.ty == "message-created" && !m.id.is_empty());
+        let id = match message {
+            Some(message) => Id::Message(message.id.clone()),
+            None if event.id != 0 => Id::Event(event.id),
+            None => return None,
+        };
+        Some(Self {
+            platform: event.platform.clone(),
+            self_id: event.self_id.clone(),
+            ty: event.ty.clone(),
+            id,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Seen {
+    keys: HashSet<Key>,
+    order: VecDeque<(Instant, Key)>,
+}
+
+/// Recognizes events delivered more than once, e.g. by redundant SDKs or
+/// after a session resumed.
+#[derive(Debug, Default)]
+pub struct Dedup {
+    window: Option<Duration>,
+    seen: Mutex<Seen>,
+}
+
+impl Dedup {
+    /// Remember events for `window`; never drops any if `None`.
+    pub fn new(window: Option<Duration>) -> Self {
+        Self {
+            window,
+            ..Default::default()
+        }
+    }
+
+    /// Whether `event` was seen within the window, remembering it if not.
+    ///
+    /// Internal events and events without any id are never duplicates.
+    pub fn is_duplicate(&self, event: &Event) -> bool {
+        let Some(window) = self.window else {
+            return false;
+        };
+        let Some(key) = Key::of(event) else {
+            return false;
+        };
+        let now = Instant::now();
+        let mut seen = self.seen.lock().unwrap();
+        while let Some((at, _)) = seen.order.front() {
+            if now.duration_since(*at) < window {
+                break;
+            }
+            let (_, old) = seen.order.pop_front().unwrap();
+            seen.keys.remove(&old);
+        }
+        if !seen.keys.insert(key.clone()) {
+            return true;
+        }
+        seen.order.push_back((now, key));
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Dedup;
+    use crate::structs::{Event, Message};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_dedup() {
+        let dedup = Dedup::new(Some(Duration::from_secs(60)));
+        let event = |id: i64, message: Option<&str>| Event {
+            id,
+            ty: "message-created".to_string(),
+            platform: "test".to_string(),
+            self_id: "1".to_string(),
+            message: message.map(|id| Message {
+                id: id.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        assert!(!dedup.is_duplicate(&event(1, Some("m"))));
+        // another upstream numbers its events differently
+        assert!(dedup.is_duplicate(&event(7, Some("m"))));
+        assert!(!dedup.is_duplicate(&event(2, None)));
+        assert!(dedup.is_duplicate(&event(2, None)));
+        assert!(!dedup.is_duplicate(&event(0, None)));
+        assert!(!dedup.is_duplicate(&event(0, None)));
+
+        // reactions and edits of one message are distinct events
+        let on_message = |ty: &str, id: i64| Event {
+            ty: ty.to_string(),
+            ..event(id, Some("m"))
+        };
+        assert!(!dedup.is_duplicate(&on_message("reaction-added", 10)));
+        assert!(!dedup.is_duplicate(&on_message("reaction-added", 11)));
+        assert!(dedup.is_duplicate(&on_message("reaction-added", 11)));
+        assert!(!dedup.is_duplicate(&on_message("message-updated", 12)));
+        assert!(!dedup.is_duplicate(&on_message("message-updated", 13)));
+        assert!(!dedup.is_duplicate(&on_message("message-deleted", 14)));
+
+        tokio::time::advance(Duration::from_secs(61)).await;
+        assert!(!dedup.is_duplicate(&event(1, Some("m"))));
+        assert!(!Dedup::default().is_duplicate(&event(1, Some("m"))));
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, Semaphore};
+
+use crate::structs::Event;
+
+/// Which events an app must handle one after another.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EventOrdering {
+    /// No ordering, events are handled as soon as a slot is free.
+    #[default]
+    None,
+    /// Events in the same channel are handled in order.
+    PerChannel,
+    /// Events from the same user are handled in order.
+    PerUser,
+    /// All events are handled in order.
+    Global,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct DispatchOptions {
+    pub ordering: EventOrdering,
+    /// Maximum number of events handled at once by each app.
+    pub concurrency: usize,
+}
+
+impl Default for DispatchOptions {
+    fn default() -> Self {
+        Self {
+            ordering: EventOrdering::None,
+            concurrency: 64,
+        }
+    }
+}
+
+type Queues = Mutex<HashMap<String, (u64, oneshot::Receiver<()>)>>;
+
+/// Runs event handlers of a single app.
+#[derive(Debug)]
+pub struct Dispatcher {
+    ordering: EventOrdering,
+    concurrency: usize,
+    semaphore: Arc<Semaphore>,
+    queues: Arc<Queues>,
+    seq: AtomicU64,
+}
+
+impl Dispatcher {
+    pub fn new(options: DispatchOptions) -> Self {
+        let concurrency = options.concurrency.max(1);
+        Self {
+            ordering: options.ordering,
+            concurrency,
+            semaphore: Arc::new(Semaphore::new(concurrency)),
+            queues: Default::default(),
+            seq: AtomicU64::new(0),
+        }
+    }
+
+    fn key(&self, event: &Event) -> Option<String> {
+        let scope = |id: &str| format!("{}:{}:{}", event.platform, event.self_id, id);
+        match self.ordering {
+            EventOrdering::None => None,
+            EventOrdering::PerChannel => event
+                .channel
+                .as_ref()
+                .or(event.message.as_ref().and_then(|m| m.channel.as_ref()))
+                .map(|c| scope(&c.id)),
+            EventOrdering::PerUser => event
+                .user
+                .as_ref()
+                .or(event.message.as_ref().and_then(|m| m.user.as_ref()))
+                .map(|u| scope(&u.id)),
+            EventOrdering::Global => Some(String::new()),
+        }
+    }
+
+    /// Number of events currently being handled.
+    pub fn in_flight(&self) -> usize {
+        self.concurrency - self.semaphore.available_permits()
+    }
+
+    /// Wait for a free slot, then run `task` in the background.
+    ///
+    /// Tasks sharing an ordering key start in the order they were dispatched,
+    /// each after the previous one has finished. Tasks queued behind a busy
+    /// key are accepted at once and only take a slot when their turn comes.
+    pub async fn dispatch<F>(&self, event: &Event, task: F)
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let Some(key) = self.key(event) else {
+            let permit = self.semaphore.clone().acquire_owned().await.unwrap();
+            tokio::spawn(async move {
+                task.await;
+                drop(permit);
+            });
+            return;
+        };
+
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel::<()>();
+        let prev = self
+            .queues
+            .lock()
+            .unwrap()
+            .insert(key.clone(), (seq, rx))
+            .map(|(_, prev)| prev);
+        let permit = match prev {
+            Some(_) => None,
+            None => Some(self.semaphore.clone().acquire_owned().await.unwrap()),
+        };
+        let semaphore = self.semaphore.clone();
+        let queues = self.queues.clone();
+        tokio::spawn(async move {
+            let permit = match (permit, prev) {
+                (Some(permit), _) => permit,
+                (None, prev) => {
+                    if let Some(prev) = prev {
+                        let _ = prev.await;
+                    }
+                    semaphore.acquire_owned().await.unwrap()
+                }
+            };
+            task.await;
+            drop(tx);
+            let mut queues = queues.lock().unwrap();
+            // nothing queued behind this task
+            if queues.get(&key).is_some_and(|(s, _)| *s == seq) {
+                queues.remove(&key);
+            }
+            drop(permit);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use super::{DispatchOptions, Dispatcher, EventOrdering};
+    use crate::structs::{Channel, Event};
+
+    fn event(channel: &str) -> Event {
+        Event {
+            channel: Some(Channel {
+                id: channel.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    async fn run(ordering: EventOrdering) -> Vec<usize> {
+        let dispatcher = Dispatcher::new(DispatchOptions {
+            ordering,
+            concurrency: 8,
+        });
+        let seen = Arc::new(Mutex::new(vec![]));
+        for (i, channel) in ["a", "a", "b"].into_iter().enumerate() {
+            let seen = seen.clone();
+            dispatcher
+                .dispatch(&event(channel), async move {
+                    if i == 0 {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                    seen.lock().unwrap().push(i);
+                })
+                .await;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let seen = seen.lock().unwrap().clone();
+        seen
+    }
+
+    #[tokio::test]
+    async fn test_ordering() {
+        assert_eq!(run(EventOrdering::None).await, vec![1, 2, 0]);
+        assert_eq!(run(EventOrdering::PerChannel).await, vec![2, 0, 1]);
+        assert_eq!(run(EventOrdering::Global).await, vec![0, 1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_busy_key() {
+        let dispatcher = Dispatcher::new(DispatchOptions {
+            ordering: EventOrdering::PerChannel,
+            concurrency: 2,
+        });
+        let seen = Arc::new(Mutex::new(vec![]));
+        for (i, channel) in ["a", "a", "a", "b"].into_iter().enumerate() {
+            let (event, seen) = (event(channel), seen.clone());
+            let dispatch = dispatcher.dispatch(&event, async move {
+                if i == 0 {
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                seen.lock().unwrap().push(i);
+            });
+            // queued events don't hold up others
+            tokio::time::timeout(Duration::from_millis(10), dispatch)
+                .await
+                .unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert_eq!(*seen.lock().unwrap(), vec![3]);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(*seen.lock().unwrap(), vec![3, 0, 1, 2]);
+        assert!(dispatcher.queues.lock().unwrap().is_empty());
+        assert_eq!(dispatcher.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_backpressure() {
+        let dispatcher = Dispatcher::new(DispatchOptions {
+            ordering: EventOrdering::None,
+            concurrency: 1,
+        });
+        let event = event("a");
+        dispatcher
+            .dispatch(&event, async {
+                tokio::time::sleep(Duration::from_millis(50)).await
+            })
+            .await;
+        let second = dispatcher.dispatch(&event, async {});
+        assert!(tokio::time::timeout(Duration::from_millis(10), second)
+            .await
+            .is_err());
+    }
+}
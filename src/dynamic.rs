@@ -0,0 +1,928 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, RwLock,
+    },
+};
+
+use serde_json::{json, Value};
+use tokio::task::AbortHandle;
+use tracing::{debug, info};
+
+use crate::{
+    api::{IntoRawApiCall, RawApiCall, INTERNAL_PREFIX},
+    error::{ApiError, SatoriError},
+    middleware::{ApiMiddleware, EventMiddleware},
+    report::guard,
+    routing::{login_bot, Route},
+    runtime::{Runtime, SatoriOptions},
+    session::in_app,
+    structs::{BotId, Event, Login},
+    system::{self, SystemEvent},
+    upload::{self, Upload},
+    Satori, SatoriApp, SatoriSDK, SATORI,
+};
+
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// Object-safe counterpart of [`SatoriSDK`], bound to [`DynSatori`].
+///
+/// Implemented for every [`SatoriSDK`].
+pub trait DynSdk: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn start<'a>(&'a self, s: &'a Arc<DynSatori>) -> BoxFuture<'a, ()>;
+
+    fn call_api<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>>;
+
+    fn call_internal<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>>;
+
+    fn upload<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        bot: &'a BotId,
+        file: Upload,
+    ) -> BoxFuture<'a, Result<String, SatoriError>>;
+
+    fn has_bot<'a>(&'a self, bot: &'a BotId) -> BoxFuture<'a, bool>;
+
+    fn get_logins(&self) -> BoxFuture<'_, Vec<Login>>;
+
+    fn proxy_urls(&self) -> Vec<String>;
+}
+
+impl<T> DynSdk for T
+where
+    T: SatoriSDK + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn start<'a>(&'a self, s: &'a Arc<DynSatori>) -> BoxFuture<'a, ()> {
+        Box::pin(SatoriSDK::start(self, s))
+    }
+
+    fn call_api<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>> {
+        Box::pin(SatoriSDK::call_api(self, s, bot, payload))
+    }
+
+    fn call_internal<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>> {
+        Box::pin(SatoriSDK::call_internal(self, s, bot, payload))
+    }
+
+    fn upload<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        bot: &'a BotId,
+        file: Upload,
+    ) -> BoxFuture<'a, Result<String, SatoriError>> {
+        Box::pin(SatoriSDK::upload(self, s, bot, file))
+    }
+
+    fn has_bot<'a>(&'a self, bot: &'a BotId) -> BoxFuture<'a, bool> {
+        Box::pin(SatoriSDK::has_bot(self, bot))
+    }
+
+    fn get_logins(&self) -> BoxFuture<'_, Vec<Login>> {
+        Box::pin(SatoriSDK::get_logins(self))
+    }
+
+    fn proxy_urls(&self) -> Vec<String> {
+        SatoriSDK::proxy_urls(self)
+    }
+}
+
+/// Object-safe counterpart of [`SatoriApp`], bound to [`DynSatori`].
+///
+/// Implemented for every [`SatoriApp`].
+pub trait DynApp: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn start<'a>(&'a self, s: &'a Arc<DynSatori>) -> BoxFuture<'a, ()>;
+
+    fn handle_event<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        event: Event,
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+impl<T> DynApp for T
+where
+    T: SatoriApp + Send + Sync,
+{
+    fn name(&self) -> &'static str {
+        std::any::type_name::<T>()
+    }
+
+    fn start<'a>(&'a self, s: &'a Arc<DynSatori>) -> BoxFuture<'a, ()> {
+        Box::pin(SatoriApp::start(self, s))
+    }
+
+    fn handle_event<'a>(
+        &'a self,
+        s: &'a Arc<DynSatori>,
+        event: Event,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            SatoriApp::handle_event(self, s, event)
+                .await
+                .map_err(Into::into)
+        })
+    }
+}
+
+/// Object-safe counterpart of [`Satori`], see [`AnySatori`].
+///
+/// Implemented for `Arc` of every [`Satori`].
+pub trait ErasedSatori: Send + Sync {
+    fn spawn(&self) -> BoxFuture<'_, ()>;
+
+    fn start(&self) -> BoxFuture<'_, ()>;
+
+    fn shutdown(&self) -> BoxFuture<'_, ()>;
+
+    fn call_api<'a>(
+        &'a self,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>>;
+
+    fn call_internal<'a>(
+        &'a self,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>>;
+
+    fn upload<'a>(
+        &'a self,
+        bot: &'a BotId,
+        file: Upload,
+    ) -> BoxFuture<'a, Result<String, SatoriError>>;
+
+    fn handle_event(&self, event: Event) -> BoxFuture<'_, ()>;
+
+    fn get_logins(&self) -> BoxFuture<'_, Vec<Login>>;
+
+    fn proxy_urls(&self) -> Vec<String>;
+
+    fn routes(&self) -> Vec<Route>;
+
+    fn runtime(&self) -> &Runtime;
+
+    fn stopped(&self) -> BoxFuture<'_, ()>;
+}
+
+impl<S> ErasedSatori for Arc<S>
+where
+    S: Satori + Send + Sync + 'static,
+{
+    fn spawn(&self) -> BoxFuture<'_, ()> {
+        Box::pin(Satori::spawn(self))
+    }
+
+    fn start(&self) -> BoxFuture<'_, ()> {
+        Box::pin(Satori::start(self))
+    }
+
+    fn shutdown(&self) -> BoxFuture<'_, ()> {
+        Box::pin(Satori::shutdown(self))
+    }
+
+    fn call_api<'a>(
+        &'a self,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>> {
+        Box::pin(Satori::call_api(self, bot, payload))
+    }
+
+    fn call_internal<'a>(
+        &'a self,
+        bot: &'a BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>> {
+        Box::pin(Satori::call_internal(self, bot, payload))
+    }
+
+    fn upload<'a>(
+        &'a self,
+        bot: &'a BotId,
+        file: Upload,
+    ) -> BoxFuture<'a, Result<String, SatoriError>> {
+        Box::pin(Satori::upload(self, bot, file))
+    }
+
+    fn handle_event(&self, event: Event) -> BoxFuture<'_, ()> {
+        Box::pin(Satori::handle_event(self, event))
+    }
+
+    fn get_logins(&self) -> BoxFuture<'_, Vec<Login>> {
+        Box::pin(Satori::get_logins(self))
+    }
+
+    fn proxy_urls(&self) -> Vec<String> {
+        Satori::proxy_urls(self)
+    }
+
+    fn routes(&self) -> Vec<Route> {
+        Satori::routes(self)
+    }
+
+    fn runtime(&self) -> &Runtime {
+        Satori::runtime(self)
+    }
+
+    fn stopped(&self) -> BoxFuture<'_, ()> {
+        Box::pin(Satori::stopped(self))
+    }
+}
+
+/// Any [`Satori`] as one type, for code that can't be generic over it, e.g.
+/// stored [`Command`](crate::command::Command) handlers.
+pub struct AnySatori(Box<dyn ErasedSatori>);
+
+impl AnySatori {
+    pub fn new<S>(s: &Arc<S>) -> Arc<Self>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        Arc::new(Self(Box::new(s.clone())))
+    }
+}
+
+impl Satori for AnySatori {
+    async fn spawn(self: &Arc<Self>) {
+        self.0.spawn().await
+    }
+
+    async fn start(self: &Arc<Self>) {
+        self.0.start().await
+    }
+
+    async fn shutdown(self: &Arc<Self>) {
+        self.0.shutdown().await
+    }
+
+    async fn call_api<T>(self: &Arc<Self>, bot: &BotId, payload: T) -> Result<Value, SatoriError>
+    where
+        T: IntoRawApiCall + Send,
+    {
+        self.0.call_api(bot, payload.into_raw()).await
+    }
+
+    async fn call_internal(
+        self: &Arc<Self>,
+        bot: &BotId,
+        payload: RawApiCall,
+    ) -> Result<Value, SatoriError> {
+        self.0.call_internal(bot, payload).await
+    }
+
+    async fn upload(self: &Arc<Self>, bot: &BotId, file: Upload) -> Result<String, SatoriError> {
+        self.0.upload(bot, file).await
+    }
+
+    async fn handle_event(self: &Arc<Self>, event: Event) {
+        self.0.handle_event(event).await
+    }
+
+    async fn get_logins(self: &Arc<Self>) -> Vec<Login> {
+        self.0.get_logins().await
+    }
+
+    fn proxy_urls(self: &Arc<Self>) -> Vec<String> {
+        self.0.proxy_urls()
+    }
+
+    fn routes(self: &Arc<Self>) -> Vec<Route> {
+        self.0.routes()
+    }
+
+    fn runtime(self: &Arc<Self>) -> &Runtime {
+        self.0.runtime()
+    }
+
+    async fn stopped(self: &Arc<Self>) {
+        self.0.stopped().await
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SdkId(pub usize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AppId(usize);
+
+struct Entry<T: ?Sized> {
+    id: usize,
+    inner: Arc<T>,
+    task: Option<AbortHandle>,
+}
+
+#[derive(Default)]
+pub struct SatoriBuilder {
+    sdk: Vec<Box<dyn DynSdk>>,
+    app: Vec<Box<dyn DynApp>>,
+    options: SatoriOptions,
+}
+
+impl SatoriBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn sdk<T>(mut self, sdk: T) -> Self
+    where
+        T: DynSdk + 'static,
+    {
+        self.sdk.push(Box::new(sdk));
+        self
+    }
+
+    pub fn app<T>(mut self, app: T) -> Self
+    where
+        T: DynApp + 'static,
+    {
+        self.app.push(Box::new(app));
+        self
+    }
+
+    pub fn sdks(mut self, sdk: Vec<Box<dyn DynSdk>>) -> Self {
+        self.sdk.extend(sdk);
+        self
+    }
+
+    pub fn apps(mut self, app: Vec<Box<dyn DynApp>>) -> Self {
+        self.app.extend(app);
+        self
+    }
+
+    pub fn options(mut self, options: SatoriOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub fn event_middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: EventMiddleware + Send + Sync + 'static,
+    {
+        self.options.middlewares = self.options.middlewares.event(middleware);
+        self
+    }
+
+    pub fn api_middleware<M>(mut self, middleware: M) -> Self
+    where
+        M: ApiMiddleware + Send + Sync + 'static,
+    {
+        self.options.middlewares = self.options.middlewares.api(middleware);
+        self
+    }
+
+    pub fn build(self) -> Arc<DynSatori> {
+        let next_id = AtomicUsize::new(0);
+        let sdk = self
+            .sdk
+            .into_iter()
+            .map(|sdk| Entry {
+                id: next_id.fetch_add(1, Ordering::Relaxed),
+                inner: Arc::from(sdk),
+                task: None,
+            })
+            .collect();
+        let app = self
+            .app
+            .into_iter()
+            .map(|app| Entry {
+                id: next_id.fetch_add(1, Ordering::Relaxed),
+                inner: Arc::from(app),
+                task: None,
+            })
+            .collect();
+        Arc::new(DynSatori {
+            sdk: RwLock::new(sdk),
+            app: RwLock::new(app),
+            next_id,
+            runtime: Runtime::new(self.options),
+        })
+    }
+}
+
+/// A [`Satori`] whose SDKs and apps are chosen at runtime.
+///
+/// Unlike types generated by [`satori!`](crate::satori), SDKs and apps can be
+/// added and removed while running.
+pub struct DynSatori {
+    sdk: RwLock<Vec<Entry<dyn DynSdk>>>,
+    app: RwLock<Vec<Entry<dyn DynApp>>>,
+    next_id: AtomicUsize,
+    runtime: Runtime,
+}
+
+impl DynSatori {
+    pub fn builder() -> SatoriBuilder {
+        SatoriBuilder::new()
+    }
+
+    fn sdks(&self) -> Vec<(usize, Arc<dyn DynSdk>)> {
+        let sdk = self.sdk.read().unwrap();
+        sdk.iter().map(|e| (e.id, e.inner.clone())).collect()
+    }
+
+    fn sdk_by_id(&self, id: usize) -> Option<Arc<dyn DynSdk>> {
+        let sdk = self.sdk.read().unwrap();
+        sdk.iter().find(|e| e.id == id).map(|e| e.inner.clone())
+    }
+
+    async fn probe(&self, bot: &BotId) -> Vec<usize> {
+        let mut claims = vec![];
+        for (id, sdk) in self.sdks() {
+            if sdk.has_bot(bot).await {
+                claims.push(id);
+            }
+        }
+        claims
+    }
+
+    fn apps(&self) -> Vec<(usize, Arc<dyn DynApp>)> {
+        let app = self.app.read().unwrap();
+        app.iter().map(|e| (e.id, e.inner.clone())).collect()
+    }
+
+    fn spawn_sdk(self: &Arc<Self>, sdk: Arc<dyn DynSdk>) -> AbortHandle {
+        let me = self.clone();
+        self.runtime
+            .spawn(sdk.name(), async move { sdk.start(&me).await })
+    }
+
+    fn spawn_app(self: &Arc<Self>, app: Arc<dyn DynApp>) -> AbortHandle {
+        let me = self.clone();
+        self.runtime
+            .spawn(app.name(), async move { app.start(&me).await })
+    }
+
+    pub fn add_sdk<T>(self: &Arc<Self>, sdk: T) -> SdkId
+    where
+        T: DynSdk + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let inner: Arc<dyn DynSdk> = Arc::new(sdk);
+        let mut sdk = self.sdk.write().unwrap();
+        let task = self
+            .runtime
+            .state()
+            .is_started()
+            .then(|| self.spawn_sdk(inner.clone()));
+        sdk.push(Entry { id, inner, task });
+        info!(target: SATORI, id, "sdk added");
+        SdkId(id)
+    }
+
+    pub fn add_app<T>(self: &Arc<Self>, app: T) -> AppId
+    where
+        T: DynApp + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let inner: Arc<dyn DynApp> = Arc::new(app);
+        let mut app = self.app.write().unwrap();
+        let task = self.runtime.state().is_started().then(|| {
+            self.runtime.emit(SystemEvent::AppStarted {
+                app: inner.name().to_string(),
+            });
+            self.spawn_app(inner.clone())
+        });
+        app.push(Entry { id, inner, task });
+        info!(target: SATORI, id, "app added");
+        AppId(id)
+    }
+
+    /// Remove an SDK, aborting its task if running.
+    ///
+    /// Returns `false` if the SDK has already been removed.
+    pub fn remove_sdk(&self, SdkId(id): SdkId) -> bool {
+        let mut sdk = self.sdk.write().unwrap();
+        let Some(pos) = sdk.iter().position(|e| e.id == id) else {
+            return false;
+        };
+        if let Some(task) = sdk.remove(pos).task {
+            task.abort();
+        }
+        self.runtime.routes().remove_sdk(id);
+        info!(target: SATORI, id, "sdk removed");
+        true
+    }
+
+    /// Remove an app, aborting its task if running.
+    ///
+    /// Returns `false` if the app has already been removed.
+    pub fn remove_app(&self, AppId(id): AppId) -> bool {
+        let mut app = self.app.write().unwrap();
+        let Some(pos) = app.iter().position(|e| e.id == id) else {
+            return false;
+        };
+        if let Some(task) = app.remove(pos).task {
+            task.abort();
+        }
+        self.runtime.remove_dispatcher(id);
+        info!(target: SATORI, id, "app removed");
+        true
+    }
+
+    pub async fn start_with_graceful_shutdown(self: &Arc<Self>, signal: impl Future) {
+        Satori::spawn(self).await;
+        tokio::select! {
+            _ = signal => {}
+            _ = self.runtime.join() => {}
+        }
+        Satori::shutdown(self).await
+    }
+}
+
+impl Satori for DynSatori {
+    async fn spawn(self: &Arc<Self>) {
+        let mut sdk = self.sdk.write().unwrap();
+        let mut app = self.app.write().unwrap();
+        if !self.runtime.begin_start() {
+            return;
+        }
+        for e in sdk.iter_mut() {
+            e.task = Some(self.spawn_sdk(e.inner.clone()));
+        }
+        for e in app.iter_mut() {
+            e.task = Some(self.spawn_app(e.inner.clone()));
+        }
+        self.runtime.finish_start();
+        let apps = app.iter().map(|e| e.inner.name()).collect();
+        drop((sdk, app));
+        system::started(self, apps);
+    }
+
+    async fn start(self: &Arc<Self>) {
+        Satori::spawn(self).await;
+        self.runtime.join().await;
+        Satori::shutdown(self).await;
+    }
+
+    async fn shutdown(self: &Arc<Self>) {
+        let apps = self.apps().iter().map(|(_, app)| app.name()).collect();
+        self.runtime.shutdown(system::stopping(self, apps)).await;
+    }
+
+    async fn call_api<T>(self: &Arc<Self>, bot: &BotId, payload: T) -> Result<Value, SatoriError>
+    where
+        T: IntoRawApiCall + Send,
+    {
+        let payload = payload.into_raw();
+        debug!(target: SATORI, ?bot, ?payload, "call api");
+        let me = self.clone();
+        let endpoint = move |bot: BotId, payload: RawApiCall| -> BoxFuture<'static, _> {
+            let me = me.clone();
+            Box::pin(async move {
+                let (me, bot) = (&me, &bot);
+                me.runtime
+                    .routes()
+                    .call(
+                        bot,
+                        payload,
+                        || me.probe(bot),
+                        |id, payload| async move {
+                            match me.sdk_by_id(id) {
+                                Some(sdk) => sdk.call_api(me, bot, payload).await,
+                                None => Err(SatoriError::InvalidBot),
+                            }
+                        },
+                    )
+                    .await
+            })
+        };
+        self.runtime
+            .track(self.runtime.middlewares().call_api(bot, payload, &endpoint))
+            .await
+    }
+
+    async fn call_internal(
+        self: &Arc<Self>,
+        bot: &BotId,
+        payload: RawApiCall,
+    ) -> Result<Value, SatoriError> {
+        debug!(target: SATORI, ?bot, ?payload, "call internal api");
+        let payload = RawApiCall {
+            method: format!("{INTERNAL_PREFIX}{}", payload.method),
+            body: payload.body,
+        };
+        let me = self.clone();
+        let endpoint = move |bot: BotId, mut payload: RawApiCall| -> BoxFuture<'static, _> {
+            let me = me.clone();
+            Box::pin(async move {
+                if let Some(method) = payload.method.strip_prefix(INTERNAL_PREFIX) {
+                    payload.method = method.to_string();
+                }
+                let (me, bot) = (&me, &bot);
+                me.runtime
+                    .routes()
+                    .call(
+                        bot,
+                        payload,
+                        || me.probe(bot),
+                        |id, payload| async move {
+                            match me.sdk_by_id(id) {
+                                Some(sdk) => sdk.call_internal(me, bot, payload).await,
+                                None => Err(SatoriError::InvalidBot),
+                            }
+                        },
+                    )
+                    .await
+            })
+        };
+        self.runtime
+            .track(self.runtime.middlewares().call_api(bot, payload, &endpoint))
+            .await
+    }
+
+    async fn upload(self: &Arc<Self>, bot: &BotId, file: Upload) -> Result<String, SatoriError> {
+        debug!(target: SATORI, ?bot, filename = file.filename, "upload");
+        // middlewares see the file's metadata, the endpoint uploads the file
+        let payload = RawApiCall {
+            method: "upload.create".to_string(),
+            body: json!({ "filename": file.filename, "mime": file.mime }),
+        };
+        let me = self.clone();
+        let endpoint = move |bot: BotId, payload: RawApiCall| -> BoxFuture<'static, _> {
+            let (me, file) = (me.clone(), file.clone());
+            Box::pin(async move {
+                let (me, bot) = (&me, &bot);
+                let result = me
+                    .runtime
+                    .routes()
+                    .call(
+                        bot,
+                        payload,
+                        || me.probe(bot),
+                        |id, _| {
+                            let file = file.clone();
+                            async move {
+                                match me.sdk_by_id(id) {
+                                    Some(sdk) => sdk.upload(me, bot, file).await,
+                                    None => Err(SatoriError::InvalidBot),
+                                }
+                            }
+                        },
+                    )
+                    .await;
+                let url = match result {
+                    Err(SatoriError::ApiError(ApiError::NotFound)) => {
+                        me.runtime.uploads().put(bot, file).await?
+                    }
+                    result => result?,
+                };
+                Ok(Value::String(url))
+            })
+        };
+        let url = self
+            .runtime
+            .track(self.runtime.middlewares().call_api(bot, payload, &endpoint))
+            .await?;
+        upload::into_url(url)
+    }
+
+    async fn handle_event(self: &Arc<Self>, event: Event) {
+        debug!(target: SATORI, ?event, "handle event");
+        if !self.runtime.accepts(&event) {
+            debug!(target: SATORI, id = event.id, state = ?self.runtime.state(), "event dropped");
+            return;
+        }
+        if self.runtime.dedup().is_duplicate(&event) {
+            debug!(target: SATORI, id = event.id, "duplicate event dropped");
+            return;
+        }
+        if let Some(bot) = login_bot(&event) {
+            let claims = self.probe(&bot).await;
+            self.runtime.routes().set_claims(&bot, claims);
+        }
+        let me = self.clone();
+        let endpoint = move |event: Event| -> BoxFuture<'static, ()> {
+            let me = me.clone();
+            Box::pin(async move {
+                let taken = me.runtime.waiters().offer(&event);
+                for (id, app) in me.apps() {
+                    if taken.is_some_and(|t| t.skips(id)) {
+                        continue;
+                    }
+                    let task = me.runtime.track(guard(me.clone(), app.name(), &event, {
+                        let me = me.clone();
+                        let event = event.clone();
+                        async move { in_app(id, app.handle_event(&me, event)).await }
+                    }));
+                    me.runtime.dispatcher(id).dispatch(&event, task).await;
+                }
+            })
+        };
+        self.runtime
+            .middlewares()
+            .handle_event(event, &endpoint)
+            .await
+    }
+
+    async fn get_logins(self: &Arc<Self>) -> Vec<Login> {
+        let mut result = vec![];
+        for (id, sdk) in self.sdks() {
+            let mut logins = sdk.get_logins().await;
+            self.runtime.routes().claim_logins(id, &logins);
+            result.append(&mut logins);
+        }
+        result
+    }
+
+    fn proxy_urls(self: &Arc<Self>) -> Vec<String> {
+        self.sdks()
+            .iter()
+            .flat_map(|(_, sdk)| sdk.proxy_urls())
+            .collect()
+    }
+
+    fn routes(self: &Arc<Self>) -> Vec<Route> {
+        self.runtime.routes().routes()
+    }
+
+    fn runtime(self: &Arc<Self>) -> &Runtime {
+        &self.runtime
+    }
+
+    async fn stopped(self: &Arc<Self>) {
+        self.runtime.stopped().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        time::Duration,
+    };
+
+    use serde_json::{json, Value};
+
+    use super::DynSatori;
+    use crate::{
+        api::RawApiCall,
+        error::{AppError, SatoriError},
+        report::ErrorHook,
+        runtime::SatoriOptions,
+        structs::{BotId, Event, Login},
+        system::SystemEvent,
+        Satori, SatoriApp, SatoriSDK,
+    };
+
+    struct Mock(&'static str, Arc<AtomicUsize>);
+
+    impl SatoriSDK for Mock {
+        async fn start<S>(&self, s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            self.1.fetch_add(1, Ordering::SeqCst);
+            s.stopped().await;
+        }
+
+        async fn call_api<S>(
+            &self,
+            _s: &Arc<S>,
+            _bot: &BotId,
+            _payload: RawApiCall,
+        ) -> Result<Value, SatoriError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            Ok(json!(self.0))
+        }
+
+        async fn has_bot(&self, bot: &BotId) -> bool {
+            bot.platform == self.0
+        }
+
+        async fn get_logins(&self) -> Vec<Login> {
+            vec![]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hot_swap() {
+        let started = Arc::new(AtomicUsize::new(0));
+        let s = DynSatori::builder().sdk(Mock("a", started.clone())).build();
+        let bot = |platform: &str| BotId {
+            id: "1".to_string(),
+            platform: platform.to_string(),
+        };
+        let call = || RawApiCall {
+            method: "test".to_string(),
+            body: json!(null),
+        };
+
+        s.spawn().await;
+        let id = s.add_sdk(Mock("b", started.clone()));
+        tokio::task::yield_now().await;
+        assert_eq!(started.load(Ordering::SeqCst), 2);
+        assert_eq!(s.call_api(&bot("b"), call()).await.unwrap(), json!("b"));
+
+        assert!(s.remove_sdk(id));
+        assert!(!s.remove_sdk(id));
+        assert!(matches!(
+            s.call_api(&bot("b"), call()).await,
+            Err(SatoriError::InvalidBot)
+        ));
+        assert_eq!(s.call_api(&bot("a"), call()).await.unwrap(), json!("a"));
+
+        s.shutdown().await;
+    }
+
+    struct Panic(Arc<Mutex<Vec<String>>>);
+
+    impl SatoriApp for Panic {
+        type Error = Infallible;
+
+        async fn start<S>(&self, _s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+        }
+
+        async fn handle_event<S>(&self, _s: &Arc<S>, event: Event) -> Result<(), Infallible>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            if SystemEvent::from_event(&event).is_none() {
+                panic!("boom {}", event.id)
+            }
+            self.0.lock().unwrap().push(event.internal_type.unwrap());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_report_panic() {
+        let reports = Arc::new(Mutex::new(vec![]));
+        let system = Arc::new(Mutex::new(vec![]));
+        let s = DynSatori::builder()
+            .app(Panic(system.clone()))
+            .options(SatoriOptions {
+                error_hooks: vec![ErrorHook::Custom({
+                    let reports = reports.clone();
+                    Arc::new(move |r| {
+                        let AppError::Panic(msg) = &r.error else {
+                            unreachable!()
+                        };
+                        reports.lock().unwrap().push((r.event_id, msg.clone()));
+                    })
+                })],
+                ..Default::default()
+            })
+            .build();
+        s.spawn().await;
+
+        s.handle_event(Event {
+            id: 42,
+            ..Default::default()
+        })
+        .await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert_eq!(*reports.lock().unwrap(), vec![(42, "boom 42".to_string())]);
+
+        s.shutdown().await;
+        assert_eq!(
+            *system.lock().unwrap(),
+            vec![
+                "satori/app-started",
+                "satori/shutdown-requested",
+                "satori/app-stopping"
+            ]
+        );
+    }
+}
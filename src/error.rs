@@ -1,3 +1,5 @@
+use std::time::Duration;
+
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -14,6 +16,9 @@ pub enum ApiError {
     MethodNotAllowed,
     #[error("server error ({0})")]
     ServerError(u16),
+    /// Too many calls; retry after the delay if known.
+    #[error("rate limited")]
+    RateLimited(Option<Duration>),
 }
 
 #[cfg(feature = "reqwest")]
@@ -28,6 +33,13 @@ impl ApiError {
             reqwest::StatusCode::FORBIDDEN => Ok(Self::Forbidden),
             reqwest::StatusCode::NOT_FOUND => Ok(Self::NotFound),
             reqwest::StatusCode::METHOD_NOT_ALLOWED => Ok(Self::MethodNotAllowed),
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = resp
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(retry_after);
+                Ok(Self::RateLimited(retry_after))
+            }
             s if s.is_server_error() => Ok(Self::ServerError(s.as_u16())),
             _ => Err(SatoriError::InternalError(anyhow::anyhow!(
                 "unexpected status code"
@@ -36,6 +48,23 @@ impl ApiError {
     }
 }
 
+/// The delay of a `Retry-After` header, in seconds or until an HTTP date.
+#[cfg(feature = "reqwest")]
+fn retry_after(value: &reqwest::header::HeaderValue) -> Option<Duration> {
+    use headers::Header;
+
+    if let Some(secs) = value.to_str().ok().and_then(|v| v.parse().ok()) {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = headers::Date::decode(&mut std::iter::once(value)).ok()?;
+    let date = std::time::SystemTime::from(date);
+    // a date in the past means retrying right away
+    Some(
+        date.duration_since(std::time::SystemTime::now())
+            .unwrap_or_default(),
+    )
+}
+
 #[derive(Debug, Error)]
 pub enum SatoriError {
     #[error(transparent)]
@@ -46,6 +75,46 @@ pub enum SatoriError {
     InternalError(#[from] anyhow::Error),
 }
 
+impl SatoriError {
+    /// Whether the call may succeed if simply tried again: server errors,
+    /// rate limits and failures to reach the platform.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::ApiError(ApiError::ServerError(_) | ApiError::RateLimited(_)) => true,
+            Self::InternalError(e) => is_transport(e),
+            _ => false,
+        }
+    }
+}
+
+fn is_transport(e: &anyhow::Error) -> bool {
+    // the connection went away before the answer arrived
+    if e.is::<tokio::sync::oneshot::error::RecvError>() {
+        return true;
+    }
+    #[cfg(feature = "reqwest")]
+    if let Some(e) = e.downcast_ref::<reqwest::Error>() {
+        return e.is_connect() || e.is_timeout();
+    }
+    #[cfg(any(feature = "net-sdk", feature = "onebot11"))]
+    if let Some(e) = e.downcast_ref::<tokio_tungstenite::tungstenite::Error>() {
+        use tokio_tungstenite::tungstenite::Error;
+        return matches!(
+            e,
+            Error::ConnectionClosed | Error::AlreadyClosed | Error::Io(_)
+        );
+    }
+    false
+}
+
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error(transparent)]
+    Error(#[from] anyhow::Error),
+    #[error("panicked: {0}")]
+    Panic(String),
+}
+
 pub trait MapSatoriError<T> {
     fn map_internal_error(self) -> Result<T, SatoriError>;
 }
@@ -58,3 +127,67 @@ where
         self.map_err(|e| SatoriError::InternalError(anyhow::Error::new(e)))
     }
 }
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+    #[error("missing argument <{0}>")]
+    MissingArgument(String),
+    #[error("too many arguments")]
+    TooManyArguments,
+    #[error("unknown option {0}")]
+    UnknownOption(String),
+    #[error("option --{0} needs a value")]
+    MissingOptionValue(String),
+    #[error("invalid value for {name}: expected {kind}, got {value:?}")]
+    InvalidValue {
+        name: String,
+        kind: &'static str,
+        value: String,
+    },
+    #[error("unclosed quote")]
+    UnclosedQuote,
+}
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error("timed out waiting for a reply")]
+    Timeout,
+    #[error("session cancelled")]
+    Cancelled,
+    #[error("event has no {0}")]
+    MissingField(&'static str),
+    #[error(transparent)]
+    Satori(#[from] SatoriError),
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("rate limit rule {methods:?} needs a positive rate, got {rate}")]
+    InvalidRate { methods: String, rate: f64 },
+}
+
+#[cfg(all(test, feature = "reqwest"))]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use headers::Header;
+    use reqwest::header::HeaderValue;
+
+    use super::retry_after;
+
+    #[test]
+    fn test_retry_after() {
+        let value = HeaderValue::from_static("120");
+        assert_eq!(retry_after(&value), Some(Duration::from_secs(120)));
+
+        let mut values = vec![];
+        let date = SystemTime::now() + Duration::from_secs(60);
+        headers::Date::from(date).encode(&mut values);
+        let delay = retry_after(&values[0]).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+
+        let past = HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(retry_after(&past), Some(Duration::ZERO));
+        assert_eq!(retry_after(&HeaderValue::from_static("soon")), None);
+    }
+}
@@ -0,0 +1,162 @@
+use crate::structs::{BotId, ChannelType, Event};
+
+/// A predicate on events, see [`Routed`](crate::router::Routed).
+///
+/// Closures `Fn(&Event) -> bool` are filters too.
+pub trait Filter: Send + Sync {
+    fn matches(&self, event: &Event) -> bool;
+
+    fn and<F>(self, other: F) -> And<Self, F>
+    where
+        Self: Sized,
+        F: Filter,
+    {
+        And(self, other)
+    }
+
+    fn or<F>(self, other: F) -> Or<Self, F>
+    where
+        Self: Sized,
+        F: Filter,
+    {
+        Or(self, other)
+    }
+
+    fn not(self) -> Not<Self>
+    where
+        Self: Sized,
+    {
+        Not(self)
+    }
+}
+
+impl<T> Filter for T
+where
+    T: Fn(&Event) -> bool + Send + Sync,
+{
+    fn matches(&self, event: &Event) -> bool {
+        self(event)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct And<A, B>(A, B);
+
+impl<A: Filter, B: Filter> Filter for And<A, B> {
+    fn matches(&self, event: &Event) -> bool {
+        self.0.matches(event) && self.1.matches(event)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Or<A, B>(A, B);
+
+impl<A: Filter, B: Filter> Filter for Or<A, B> {
+    fn matches(&self, event: &Event) -> bool {
+        self.0.matches(event) || self.1.matches(event)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Not<A>(A);
+
+impl<A: Filter> Filter for Not<A> {
+    fn matches(&self, event: &Event) -> bool {
+        !self.0.matches(event)
+    }
+}
+
+fn channel_id(event: &Event) -> Option<&str> {
+    let message = event.message.as_ref();
+    let channel = event
+        .channel
+        .as_ref()
+        .or(message.and_then(|m| m.channel.as_ref()))?;
+    Some(&channel.id)
+}
+
+fn guild_id(event: &Event) -> Option<&str> {
+    let message = event.message.as_ref();
+    let guild = event
+        .guild
+        .as_ref()
+        .or(message.and_then(|m| m.guild.as_ref()))?;
+    Some(&guild.id)
+}
+
+fn user_id(event: &Event) -> Option<&str> {
+    let message = event.message.as_ref();
+    let user = event
+        .user
+        .as_ref()
+        .or(message.and_then(|m| m.user.as_ref()))?;
+    Some(&user.id)
+}
+
+fn content(event: &Event) -> Option<&str> {
+    event.message.as_ref()?.content.as_deref()
+}
+
+/// Every event.
+pub fn any() -> impl Filter + Clone {
+    |_: &Event| true
+}
+
+pub fn platform(platform: impl Into<String>) -> impl Filter + Clone {
+    let platform = platform.into();
+    move |event: &Event| event.platform == platform
+}
+
+pub fn bot(bot: BotId) -> impl Filter + Clone {
+    move |event: &Event| event.platform == bot.platform && event.self_id == bot.id
+}
+
+/// Events of type `ty`, e.g. `"message-created"`.
+pub fn event_type(ty: impl Into<String>) -> impl Filter + Clone {
+    let ty = ty.into();
+    move |event: &Event| event.ty == ty
+}
+
+pub fn guild(id: impl Into<String>) -> impl Filter + Clone {
+    let id = id.into();
+    move |event: &Event| guild_id(event) == Some(id.as_str())
+}
+
+pub fn channel(id: impl Into<String>) -> impl Filter + Clone {
+    let id = id.into();
+    move |event: &Event| channel_id(event) == Some(id.as_str())
+}
+
+pub fn user(id: impl Into<String>) -> impl Filter + Clone {
+    let id = id.into();
+    move |event: &Event| user_id(event) == Some(id.as_str())
+}
+
+/// Events in private chats: direct channels, or channels outside any guild.
+pub fn direct() -> impl Filter + Clone {
+    |event: &Event| {
+        let direct = event
+            .channel
+            .as_ref()
+            .is_some_and(|c| matches!(c.ty, Some(ChannelType::Direct)));
+        direct || guild_id(event).is_none()
+    }
+}
+
+/// Events in guild channels.
+pub fn group() -> impl Filter + Clone {
+    direct().not()
+}
+
+/// Messages mentioning the bot receiving them.
+pub fn mention_self() -> impl Filter + Clone {
+    |event: &Event| {
+        content(event).is_some_and(|c| c.contains(&format!(r#"<at id="{}""#, event.self_id)))
+    }
+}
+
+/// Messages whose content matches `regex`.
+#[cfg(feature = "regex")]
+pub fn content_regex(regex: regex::Regex) -> impl Filter + Clone {
+    move |event: &Event| content(event).is_some_and(|c| regex.is_match(c))
+}
@@ -1,37 +1,97 @@
 use std::{
+    collections::HashSet,
+    convert::Infallible,
     net::{IpAddr, Ipv4Addr, SocketAddr},
-    sync::Arc,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use axum::{
-    extract::{FromRef, Path, State, WebSocketUpgrade},
-    response::IntoResponse,
+    body::Body,
+    extract::{
+        ws::{CloseFrame, WebSocket},
+        FromRef, FromRequest, Multipart, Path, RawQuery, State, WebSocketUpgrade,
+    },
+    http::Request,
+    response::{IntoResponse, Response},
     Json, TypedHeader,
 };
+use axum_server::{tls_rustls::RustlsConfig, Handle};
 use futures_util::StreamExt;
 use headers::{authorization::Bearer, Authorization, Header};
 use http::{HeaderName, HeaderValue, StatusCode};
+use reqwest::{redirect, Url};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
-use tokio::sync::broadcast;
-use tracing::{error, info};
+use serde_json::{json, Value};
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time::{sleep_until, timeout, Instant},
+};
+use tracing::{debug, error, info, warn};
 
-use super::{Signal, NET};
+use super::{secret_eq, webhook::Webhooks, Signal, Webhook, NET};
 use crate::{
     api::RawApiCall,
-    error::{ApiError, SatoriError},
-    structs::{BotId, Event},
-    SatoriApp, Satori,
+    authority::{method_matches, Caller},
+    error::{ApiError, MapSatoriError, SatoriError},
+    routing::login_bot,
+    structs::{BotId, Event, Login, Status},
+    system::{is_internal, SystemEvent},
+    upload::Upload,
+    Satori, SatoriApp,
 };
 
 type WsMessage = axum::extract::ws::Message;
 
+/// Clients must identify within this long after connecting.
+#[cfg(not(test))]
+const IDENTIFY_TIMEOUT: Duration = Duration::from_secs(10);
+#[cfg(test)]
+const IDENTIFY_TIMEOUT: Duration = Duration::from_millis(200);
+/// Clients are dropped after this long without sending anything.
+#[cfg(not(test))]
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_secs(30);
+#[cfg(test)]
+const HEARTBEAT_TIMEOUT: Duration = Duration::from_millis(300);
+
+const CLOSE_UNAUTHORIZED: u16 = 3000;
+const CLOSE_FORBIDDEN: u16 = 3003;
+const CLOSE_TIMEOUT: u16 = 3008;
+
+async fn close(socket: &mut WebSocket, code: u16, reason: &'static str) -> Result<(), axum::Error> {
+    debug!(target: NET, code, "closing WebSocket client: {reason}");
+    socket
+        .send(WsMessage::Close(Some(CloseFrame {
+            code,
+            reason: reason.into(),
+        })))
+        .await
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct NetAppConfig {
     pub host: IpAddr,
     pub port: u16,
     pub path: Option<String>,
+    /// A token with full access, named `default`.
     pub token: Option<String>,
+    #[serde(default)]
+    pub tokens: Vec<NetAppToken>,
+    /// Webhooks registered on start, besides those added with
+    /// `webhook.create`.
+    #[serde(default)]
+    pub webhooks: Vec<Webhook>,
+    /// Serve HTTPS and WSS instead of plain HTTP.
+    #[serde(default)]
+    pub tls: Option<NetAppTls>,
+}
+
+/// PEM files of the server certificate chain and private key.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NetAppTls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
 }
 
 impl Default for NetAppConfig {
@@ -41,80 +101,269 @@ impl Default for NetAppConfig {
             port: 5140,
             path: None,
             token: None,
+            tokens: vec![],
+            webhooks: vec![],
+            tls: None,
         }
     }
 }
 
+impl NetAppConfig {
+    fn all_tokens(&self) -> Arc<[NetAppToken]> {
+        let default = self.token.clone().map(|token| NetAppToken {
+            name: "default".to_string(),
+            token,
+            bots: None,
+            methods: all_methods(),
+            events: true,
+        });
+        default.into_iter().chain(self.tokens.clone()).collect()
+    }
+}
+
+/// A named client token and what it grants.
+///
+/// Once any token is configured, clients must present one of them.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NetAppToken {
+    pub name: String,
+    pub token: String,
+    /// Bots the token may call APIs as and receive events of; all if `None`.
+    #[serde(default)]
+    pub bots: Option<Vec<BotId>>,
+    /// API method patterns the token may call, e.g. `message.*`. Internal
+    /// APIs are named `internal/<method>`, the proxy route `proxy`.
+    #[serde(default = "all_methods")]
+    pub methods: Vec<String>,
+    /// Whether the token may subscribe to `/v1/events`.
+    #[serde(default = "yes")]
+    pub events: bool,
+}
+
+fn all_methods() -> Vec<String> {
+    vec!["*".to_string()]
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl NetAppToken {
+    fn allows_bot(&self, platform: &str, id: &str) -> bool {
+        self.bots
+            .as_ref()
+            .is_none_or(|bots| bots.iter().any(|b| b.platform == platform && b.id == id))
+    }
+
+    fn allows_login(&self, login: &Login) -> bool {
+        let platform = login.platform.as_deref().unwrap_or_default();
+        let id = login.self_id.as_deref().unwrap_or_default();
+        self.allows_bot(platform, id)
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.methods.iter().any(|p| method_matches(p, method))
+    }
+}
+
+/// The token matching `presented`, or `None` if no tokens are configured.
+fn authenticate<'a>(
+    tokens: &'a [NetAppToken],
+    presented: Option<&str>,
+) -> Result<Option<&'a NetAppToken>, ApiError> {
+    if tokens.is_empty() {
+        return Ok(None);
+    }
+    let presented = presented.ok_or(ApiError::Unauthorized)?;
+    tokens
+        .iter()
+        .find(|t| secret_eq(&t.token, presented))
+        .map(Some)
+        .ok_or(ApiError::Forbidden)
+}
+
+/// Whether `url` is under `prefix`. Both are compared parsed, so `..`
+/// segments, userinfo and look-alike hosts can't escape the prefix.
+fn proxy_allows(prefix: &str, url: &Url) -> bool {
+    let Ok(prefix) = Url::parse(prefix) else {
+        return false;
+    };
+    if !url.username().is_empty() || url.password().is_some() {
+        return false;
+    }
+    if url.scheme() != prefix.scheme()
+        || url.host() != prefix.host()
+        || url.port_or_known_default() != prefix.port_or_known_default()
+    {
+        return false;
+    }
+    let segments = |url: &Url| -> Vec<String> {
+        url.path_segments()
+            .map(|s| s.filter(|s| !s.is_empty()).map(Into::into).collect())
+            .unwrap_or_default()
+    };
+    segments(url).starts_with(&segments(&prefix))
+}
+
 #[derive(Debug)]
 pub struct NetApp {
     config: NetAppConfig,
     tx: broadcast::Sender<Event>,
+    webhooks: Arc<Webhooks>,
+    /// Bots clients were told about with `login-added`.
+    online: Mutex<HashSet<BotId>>,
+}
+
+async fn find_login<S>(s: &Arc<S>, bot: &BotId) -> Option<Login>
+where
+    S: Satori + Send + Sync + 'static,
+{
+    s.get_logins().await.into_iter().find(|l| {
+        l.platform.as_deref() == Some(&bot.platform) && l.self_id.as_deref() == Some(&bot.id)
+    })
+}
+
+fn login_event(ty: &str, bot: &BotId, login: Login) -> Event {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64;
+    Event {
+        ty: ty.to_string(),
+        platform: bot.platform.clone(),
+        self_id: bot.id.clone(),
+        timestamp,
+        login: Some(login),
+        ..Default::default()
+    }
 }
 
 impl NetApp {
     pub fn new(config: NetAppConfig) -> Self {
         let (tx, _) = broadcast::channel(128);
-        Self { config, tx }
+        Self {
+            config,
+            tx,
+            webhooks: Default::default(),
+            online: Default::default(),
+        }
+    }
+
+    /// Send `event` to WebSocket clients and webhooks.
+    fn push(&self, event: Event) {
+        self.webhooks.send(&event);
+        self.tx.send(event).ok();
     }
 
     async fn ws_handler<S>(
         ws: WebSocketUpgrade,
         State(s): State<Arc<S>>,
-        State(token): State<Option<String>>,
+        State(tokens): State<Arc<[NetAppToken]>>,
         State(tx): State<broadcast::Sender<Event>>,
     ) -> impl IntoResponse
     where
         S: Satori + Send + Sync + 'static,
     {
-        let mut rx = tx.subscribe();
         ws.on_upgrade(|mut socket| async move {
             info!(target: NET, "new WebSocket client acceptted.");
-            loop {
-                tokio::select! {
-                    Ok(event) = rx.recv() => {
-                        if let Err(e) = socket
-                            .send(Signal::event(event).to_string().into())
-                            .await
-                        {
-                            error!(target: NET, "Send event error: {:?}", e);
-                            break;
+            if let Err(e) = Self::ws_session(&s, &tokens, &tx, &mut socket).await {
+                debug!(target: NET, "WebSocket client closed: {e}");
+            }
+            let _ = socket.close().await;
+        })
+    }
+
+    async fn ws_session<S>(
+        s: &Arc<S>,
+        tokens: &[NetAppToken],
+        tx: &broadcast::Sender<Event>,
+        socket: &mut WebSocket,
+    ) -> Result<(), axum::Error>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let identify = tokio::select! {
+            identify = timeout(IDENTIFY_TIMEOUT, Self::wait_identify(socket)) => identify,
+            _ = s.stopped() => return Ok(()),
+        };
+        let presented = match identify {
+            Ok(Some(presented)) => presented,
+            Ok(None) => return Ok(()),
+            Err(_) => return close(socket, CLOSE_TIMEOUT, "identify timeout").await,
+        };
+        let token = match authenticate(tokens, presented.as_deref()) {
+            Ok(token) if token.is_none_or(|t| t.events) => token,
+            Err(ApiError::Unauthorized) => {
+                return close(socket, CLOSE_UNAUTHORIZED, "token required").await
+            }
+            _ => return close(socket, CLOSE_FORBIDDEN, "forbidden").await,
+        };
+        // subscribe once authenticated, and before ready so no event is missed
+        let mut rx = tx.subscribe();
+        let mut logins = s.get_logins().await;
+        if let Some(token) = token {
+            logins.retain(|l| token.allows_login(l));
+        }
+        socket
+            .send(Signal::ready(logins).to_string().into())
+            .await?;
+
+        let mut last_seen = Instant::now();
+        loop {
+            tokio::select! {
+                event = rx.recv() => match event {
+                    Ok(event) => {
+                        if token.is_none_or(|t| t.allows_bot(&event.platform, &event.self_id)) {
+                            socket.send(Signal::event(event).to_string().into()).await?;
                         }
                     }
-                    Some(Ok(msg)) = socket.next() => {
-                        match msg {
-                            WsMessage::Close(_) => break,
-                            WsMessage::Ping(b) => {
-                                socket.send(WsMessage::Pong(b)).await.ok();
+                    Err(RecvError::Lagged(n)) => {
+                        warn!(target: NET, "WebSocket client lagged, {n} events dropped")
+                    }
+                    Err(RecvError::Closed) => return Ok(()),
+                },
+                msg = socket.next() => {
+                    last_seen = Instant::now();
+                    match msg {
+                        Some(Ok(WsMessage::Text(text))) => match serde_json::from_str(&text) {
+                            Ok(Signal::Ping { .. }) => {
+                                socket.send(Signal::pong().to_string().into()).await?
                             }
-                            WsMessage::Text(text) => match serde_json::from_str(&text) {
-                                Ok(Signal::Ping { .. }) => socket
-                                    .send(Signal::pong().to_string().into())
-                                    .await
-                                    .unwrap(), //todo
-                                Ok(Signal::Identify { body, .. }) => {
-                                    if let Some(token) = &token {
-                                        if body.token.as_ref() != Some(token) {
-                                            break
-                                        }
-                                    }
-                                    socket
-                                        .send(Signal::ready(s.get_logins().await).to_string().into())
-                                        .await
-                                        .unwrap();
-                                },
-                                Ok(_) => unreachable!(),
-                                Err(e) => {
-                                    error!(target: NET, "Receive signal error: {:?}", e)
-                                }
-                            },
-                            _ => {}
-                        }
+                            Ok(_) => {}
+                            Err(e) => error!(target: NET, "Receive signal error: {:?}", e),
+                        },
+                        Some(Ok(WsMessage::Ping(b))) => socket.send(WsMessage::Pong(b)).await?,
+                        Some(Ok(WsMessage::Close(_))) | None => return Ok(()),
+                        Some(Ok(_)) => {}
+                        Some(Err(e)) => return Err(e),
                     }
-                    _ = s.stopped() => break
                 }
+                _ = sleep_until(last_seen + HEARTBEAT_TIMEOUT) => {
+                    return close(socket, CLOSE_TIMEOUT, "heartbeat timeout").await
+                }
+                _ = s.stopped() => return Ok(()),
             }
-            let _ = socket.close().await;
-        })
+        }
+    }
+
+    /// Wait for the `Identify` signal, returning its token, or `None` if the
+    /// client leaves first.
+    async fn wait_identify(socket: &mut WebSocket) -> Option<Option<String>> {
+        loop {
+            match socket.next().await? {
+                Ok(WsMessage::Text(text)) => match serde_json::from_str(&text) {
+                    Ok(Signal::Identify { body, .. }) => return Some(body.token),
+                    Ok(Signal::Ping { .. }) => {
+                        socket.send(Signal::pong().to_string().into()).await.ok()?
+                    }
+                    Ok(_) => {}
+                    Err(e) => error!(target: NET, "Receive signal error: {:?}", e),
+                },
+                Ok(WsMessage::Ping(b)) => socket.send(WsMessage::Pong(b)).await.ok()?,
+                Ok(WsMessage::Close(_)) | Err(_) => return None,
+                Ok(_) => {}
+            }
+        }
     }
 
     async fn api_handler<S>(
@@ -123,36 +372,234 @@ impl NetApp {
         TypedHeader(SelfID(id)): TypedHeader<SelfID>,
         bearer: Option<TypedHeader<Authorization<Bearer>>>,
         State(s): State<Arc<S>>,
-        State(token): State<Option<String>>,
-        Json(data): Json<Value>,
+        State(tokens): State<Arc<[NetAppToken]>>,
+        request: Request<Body>,
     ) -> Result<String, SatoriError>
     where
         S: Satori + Send + Sync + 'static,
     {
+        let presented = bearer
+            .as_ref()
+            .map(|TypedHeader(Authorization(b))| b.token());
+        let token = authenticate(&tokens, presented)?;
         if let Some(token) = token {
-            let Some(TypedHeader(Authorization(bearer))) = bearer else {
-                return Err(ApiError::Unauthorized.into());
-            };
-            if bearer.token() != token {
+            // `login.list` isn't called as any bot, and only lists allowed ones
+            let bot_allowed = api == "login.list" || token.allows_bot(&platform, &id);
+            if !bot_allowed || !token.allows_method(&api) {
                 return Err(ApiError::Forbidden.into());
             }
         }
-        s.call_api(
-            &BotId { platform, id },
+        let caller = Caller::Remote {
+            token: token.map(|t| t.name.clone()),
+        };
+        let bot = BotId { platform, id };
+        match api.as_str() {
+            "login.get" => {
+                let login = find_login(&s, &bot).await.ok_or(SatoriError::InvalidBot)?;
+                return Ok(json!(login).to_string());
+            }
+            "login.list" => {
+                let mut logins = s.get_logins().await;
+                if let Some(token) = token {
+                    logins.retain(|l| token.allows_login(l));
+                }
+                return Ok(json!(logins).to_string());
+            }
+            _ => {}
+        }
+        if api == "upload.create" {
+            let multipart = Multipart::from_request(request, &())
+                .await
+                .map_err(|e| ApiError::BadRequest(e.into()))?;
+            return caller.scope(Self::upload(&s, &bot, multipart)).await;
+        }
+        let Json(data) = Json::<Value>::from_request(request, &())
+            .await
+            .map_err(|e| ApiError::BadRequest(e.into()))?;
+        let call = s.call_api(
+            &bot,
             RawApiCall {
                 method: api,
                 body: data,
             },
-        )
-        .await
-        .map(|v| v.to_string())
+        );
+        caller.scope(call).await.map(|v| v.to_string())
+    }
+
+    /// Upload every field of `multipart`, answering field names with URLs.
+    async fn upload<S>(
+        s: &Arc<S>,
+        bot: &BotId,
+        mut multipart: Multipart,
+    ) -> Result<String, SatoriError>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let mut urls = serde_json::Map::new();
+        while let Some(field) = multipart
+            .next_field()
+            .await
+            .map_err(|e| ApiError::BadRequest(e.into()))?
+        {
+            let name = field.name().unwrap_or_default().to_string();
+            let mut file = Upload {
+                mime: field.content_type().map(ToString::to_string),
+                filename: field.file_name().map(ToString::to_string),
+                ..Default::default()
+            };
+            file.data = field
+                .bytes()
+                .await
+                .map_err(|e| ApiError::BadRequest(e.into()))?
+                .to_vec();
+            urls.insert(name, s.upload(bot, file).await?.into());
+        }
+        Ok(Value::Object(urls).to_string())
+    }
+
+    async fn internal_handler<S>(
+        Path(method): Path<String>,
+        TypedHeader(Platform(platform)): TypedHeader<Platform>,
+        TypedHeader(SelfID(id)): TypedHeader<SelfID>,
+        bearer: Option<TypedHeader<Authorization<Bearer>>>,
+        State(s): State<Arc<S>>,
+        State(tokens): State<Arc<[NetAppToken]>>,
+        Json(data): Json<Value>,
+    ) -> Result<String, SatoriError>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let presented = bearer
+            .as_ref()
+            .map(|TypedHeader(Authorization(b))| b.token());
+        let token = authenticate(&tokens, presented)?;
+        if let Some(token) = token {
+            let allowed = token.allows_method(&format!("internal/{method}"));
+            if !token.allows_bot(&platform, &id) || !allowed {
+                return Err(ApiError::Forbidden.into());
+            }
+        }
+        let caller = Caller::Remote {
+            token: token.map(|t| t.name.clone()),
+        };
+        let bot = BotId { platform, id };
+        let call = s.call_internal(&bot, RawApiCall { method, body: data });
+        caller.scope(call).await.map(|v| v.to_string())
+    }
+
+    async fn proxy_handler<S>(
+        Path(url): Path<String>,
+        RawQuery(query): RawQuery,
+        bearer: Option<TypedHeader<Authorization<Bearer>>>,
+        State(state): State<AppState<S>>,
+    ) -> Result<Response, SatoriError>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let presented = bearer
+            .as_ref()
+            .map(|TypedHeader(Authorization(b))| b.token());
+        let token = authenticate(&state.tokens, presented)?;
+        if token.is_some_and(|t| !t.allows_method("proxy")) {
+            return Err(ApiError::Forbidden.into());
+        }
+        let url = match query {
+            Some(query) => format!("{url}?{query}"),
+            None => url,
+        };
+        if let Some(rest) = url.strip_prefix("internal:") {
+            let mut parts = rest.splitn(3, '/');
+            let (platform, id) = (parts.next(), parts.next());
+            let (platform, id) = (platform.unwrap_or_default(), id.unwrap_or_default());
+            if token.is_some_and(|t| !t.allows_bot(platform, id)) {
+                return Err(ApiError::Forbidden.into());
+            }
+        }
+        if let Some(file) = state.s.runtime().uploads().get(&url) {
+            let mut response = file.data.into_response();
+            if let Some(mime) = file.mime.and_then(|m| HeaderValue::from_str(&m).ok()) {
+                response
+                    .headers_mut()
+                    .insert(http::header::CONTENT_TYPE, mime);
+            }
+            return Ok(response);
+        }
+        let url = Url::parse(&url).map_err(|e| ApiError::BadRequest(e.into()))?;
+        if !state.s.proxy_urls().iter().any(|p| proxy_allows(p, &url)) {
+            return Err(ApiError::Forbidden.into());
+        }
+        let resp = state.client.get(url).send().await.map_internal_error()?;
+        let status = StatusCode::from_u16(resp.status().as_u16()).map_internal_error()?;
+        let content_type = resp.headers().get(http::header::CONTENT_TYPE).cloned();
+        let body = resp.bytes().await.map_internal_error()?;
+        let mut response = (status, body).into_response();
+        if let Some(content_type) = content_type {
+            response
+                .headers_mut()
+                .insert(http::header::CONTENT_TYPE, content_type);
+        }
+        Ok(response)
+    }
+
+    async fn admin_handler<S>(
+        Path(api): Path<String>,
+        bearer: Option<TypedHeader<Authorization<Bearer>>>,
+        State(state): State<AppState<S>>,
+        Json(data): Json<Value>,
+    ) -> Result<String, SatoriError>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let presented = bearer
+            .as_ref()
+            .map(|TypedHeader(Authorization(b))| b.token());
+        let AppState {
+            s,
+            tokens,
+            webhooks,
+            ..
+        } = state;
+        let token = authenticate(&tokens, presented)?;
+        if token.is_some_and(|t| !t.events || !t.allows_method(&api)) {
+            return Err(ApiError::Forbidden.into());
+        }
+        match api.as_str() {
+            "webhook.create" => {
+                let mut webhook: Webhook =
+                    serde_json::from_value(data).map_err(|e| ApiError::BadRequest(e.into()))?;
+                // a webhook can't see more bots than its creator
+                if let Some(allowed) = token.and_then(|t| t.bots.as_ref()) {
+                    match &webhook.bots {
+                        None => webhook.bots = Some(allowed.clone()),
+                        Some(bots) if bots.iter().all(|b| allowed.contains(b)) => {}
+                        Some(_) => return Err(ApiError::Forbidden.into()),
+                    }
+                }
+                webhooks.add(&s, webhook);
+            }
+            "webhook.delete" => {
+                #[derive(Deserialize)]
+                struct Delete {
+                    url: String,
+                }
+                let Delete { url } =
+                    serde_json::from_value(data).map_err(|e| ApiError::BadRequest(e.into()))?;
+                if !webhooks.remove(&url) {
+                    return Err(ApiError::NotFound.into());
+                }
+            }
+            _ => return Err(ApiError::NotFound.into()),
+        }
+        Ok(Value::Null.to_string())
     }
 }
 
 struct AppState<S> {
     s: Arc<S>,
     tx: broadcast::Sender<Event>,
-    token: Option<String>,
+    tokens: Arc<[NetAppToken]>,
+    webhooks: Arc<Webhooks>,
+    client: reqwest::Client,
 }
 
 impl<S> Clone for AppState<S> {
@@ -160,7 +607,9 @@ impl<S> Clone for AppState<S> {
         Self {
             s: self.s.clone(),
             tx: self.tx.clone(),
-            token: self.token.clone(),
+            tokens: self.tokens.clone(),
+            webhooks: self.webhooks.clone(),
+            client: self.client.clone(),
         }
     }
 }
@@ -177,45 +626,150 @@ impl<S> FromRef<AppState<S>> for broadcast::Sender<Event> {
     }
 }
 
-impl<S> FromRef<AppState<S>> for Option<String> {
+impl<S> FromRef<AppState<S>> for Arc<[NetAppToken]> {
     fn from_ref(input: &AppState<S>) -> Self {
-        input.token.clone()
+        input.tokens.clone()
     }
 }
 
+/// A client for the proxy route that only follows redirects to allowed URLs.
+fn proxy_client<S>(s: &Arc<S>) -> reqwest::Client
+where
+    S: Satori + Send + Sync + 'static,
+{
+    let s = s.clone();
+    let policy = redirect::Policy::custom(move |attempt| {
+        if attempt.previous().len() >= 10 {
+            attempt.error("too many redirects")
+        } else if s
+            .proxy_urls()
+            .iter()
+            .any(|p| proxy_allows(p, attempt.url()))
+        {
+            attempt.follow()
+        } else {
+            attempt.stop()
+        }
+    });
+    reqwest::Client::builder()
+        .redirect(policy)
+        .build()
+        .unwrap()
+}
+
 impl SatoriApp for NetApp {
+    type Error = Infallible;
+
     async fn start<S>(&self, s: &Arc<S>)
     where
         S: Satori + Send + Sync + 'static,
     {
+        for webhook in &self.config.webhooks {
+            self.webhooks.add(s, webhook.clone());
+        }
         let path = self.config.path.as_deref().unwrap_or_default();
         let app = axum::Router::new()
             .route(
                 &format!("{}/v1/events", &path),
-                axum::routing::get(NetApp::ws_handler),
+                axum::routing::get(NetApp::ws_handler::<S>),
             )
             .route(
                 &format!("{}/v1/:api", &path),
-                axum::routing::post(NetApp::api_handler),
+                axum::routing::post(NetApp::api_handler::<S>),
+            )
+            .route(
+                &format!("{}/v1/internal/*method", path),
+                axum::routing::post(NetApp::internal_handler::<S>),
+            )
+            .route(
+                &format!("{}/v1/proxy/*url", path),
+                axum::routing::get(NetApp::proxy_handler::<S>),
+            )
+            .route(
+                &format!("{}/v1/admin/:api", path),
+                axum::routing::post(NetApp::admin_handler::<S>),
             )
             .with_state(AppState {
                 s: s.clone(),
                 tx: self.tx.clone(),
-                token: self.config.token.clone(),
+                tokens: self.config.all_tokens(),
+                webhooks: self.webhooks.clone(),
+                client: proxy_client(s),
             });
 
         info!(target: NET, "Starting server on {}:{}", self.config.host, self.config.port);
-        let _ = axum::Server::bind(&(self.config.host, self.config.port).into())
-            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
-            .with_graceful_shutdown(s.stopped())
-            .await;
+        let addr = SocketAddr::from((self.config.host, self.config.port));
+        let service = app.into_make_service_with_connect_info::<SocketAddr>();
+        let Some(tls) = &self.config.tls else {
+            let _ = axum::Server::bind(&addr)
+                .serve(service)
+                .with_graceful_shutdown(s.stopped())
+                .await;
+            return;
+        };
+        let config = match RustlsConfig::from_pem_file(&tls.cert, &tls.key).await {
+            Ok(config) => config,
+            Err(e) => {
+                error!(target: NET, "failed to load TLS certificate: {e}");
+                return;
+            }
+        };
+        let handle = Handle::new();
+        let server = axum_server::bind_rustls(addr, config)
+            .handle(handle.clone())
+            .serve(service);
+        tokio::pin!(server);
+        tokio::select! {
+            _ = &mut server => {}
+            _ = s.stopped() => {
+                handle.graceful_shutdown(None);
+                let _ = server.await;
+            }
+        }
     }
 
-    async fn handle_event<S>(&self, _s: &Arc<S>, event: Event)
+    async fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> Result<(), Self::Error>
     where
         S: Satori + Send + Sync + 'static,
     {
-        self.tx.send(event).ok();
+        // platforms may report logins themselves, and routing notices the
+        // rest; tell clients once either way
+        match SystemEvent::from_event(&event) {
+            Some(SystemEvent::BotLogin { bot }) => {
+                if self.online.lock().unwrap().insert(bot.clone()) {
+                    let login = find_login(s, &bot).await.unwrap_or_else(|| Login {
+                        self_id: Some(bot.id.clone()),
+                        platform: Some(bot.platform.clone()),
+                        ..Default::default()
+                    });
+                    self.push(login_event("login-added", &bot, login));
+                }
+            }
+            Some(SystemEvent::BotLogout { bot }) => {
+                if self.online.lock().unwrap().remove(&bot) {
+                    let login = Login {
+                        self_id: Some(bot.id.clone()),
+                        platform: Some(bot.platform.clone()),
+                        status: Status::Offline,
+                        ..Default::default()
+                    };
+                    self.push(login_event("login-removed", &bot, login));
+                }
+            }
+            _ if is_internal(&event) => {}
+            _ => {
+                if let Some(bot) = login_bot(&event) {
+                    let mut online = self.online.lock().unwrap();
+                    if event.ty == "login-removed" {
+                        online.remove(&bot);
+                    } else {
+                        online.insert(bot);
+                    }
+                }
+                self.push(event);
+            }
+        }
+        Ok(())
     }
 }
 
@@ -281,10 +835,198 @@ impl IntoResponse for SatoriError {
             Self::ApiError(ApiError::NotFound) => StatusCode::NOT_FOUND,
             Self::ApiError(ApiError::MethodNotAllowed) => StatusCode::METHOD_NOT_ALLOWED,
             Self::ApiError(ApiError::ServerError(code)) => StatusCode::from_u16(*code).unwrap(),
+            Self::ApiError(ApiError::RateLimited(_)) => StatusCode::TOO_MANY_REQUESTS,
             Self::InvalidBot => StatusCode::NOT_FOUND,
             Self::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         };
         let body = self.to_string();
+        if let Self::ApiError(ApiError::RateLimited(Some(delay))) = &self {
+            // whole seconds, rounded up so clients don't come back early
+            let secs = delay.as_millis().div_ceil(1000).to_string();
+            return (status, [(http::header::RETRY_AFTER, secs)], body).into_response();
+        }
         (status, body).into_response()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use reqwest::Url;
+
+    use super::{authenticate, proxy_allows, NetApp, NetAppConfig, NetAppToken};
+    use crate::{
+        dynamic::DynSatori,
+        error::ApiError,
+        structs::{BotId, Event},
+        system::SystemEvent,
+        SatoriApp,
+    };
+
+    #[test]
+    fn test_tokens() {
+        let config = NetAppConfig {
+            token: Some("main".to_string()),
+            tokens: vec![NetAppToken {
+                name: "analytics".to_string(),
+                token: "ro".to_string(),
+                bots: Some(vec![BotId {
+                    id: "1".to_string(),
+                    platform: "test".to_string(),
+                }]),
+                methods: vec!["guild.*".to_string()],
+                events: true,
+            }],
+            ..Default::default()
+        };
+        let tokens = config.all_tokens();
+
+        let main = authenticate(&tokens, Some("main")).unwrap().unwrap();
+        assert_eq!(main.name, "default");
+        assert!(main.allows_bot("test", "2") && main.allows_method("message.create"));
+
+        let ro = authenticate(&tokens, Some("ro")).unwrap().unwrap();
+        assert!(ro.allows_bot("test", "1") && !ro.allows_bot("test", "2"));
+        assert!(ro.allows_method("guild.list") && !ro.allows_method("message.create"));
+
+        assert!(matches!(
+            authenticate(&tokens, None),
+            Err(ApiError::Unauthorized)
+        ));
+        assert!(matches!(
+            authenticate(&tokens, Some("x")),
+            Err(ApiError::Forbidden)
+        ));
+        assert!(authenticate(&[], None).unwrap().is_none());
+    }
+    #[test]
+    fn test_proxy_allows() {
+        let allows = |prefix, url| proxy_allows(prefix, &Url::parse(url).unwrap());
+        assert!(allows(
+            "https://cdn.test/public/",
+            "https://cdn.test/public/a.png"
+        ));
+        assert!(allows(
+            "https://cdn.test/public",
+            "https://cdn.test:443/public/a"
+        ));
+        assert!(!allows(
+            "https://cdn.test/public/",
+            "https://cdn.test/public/../x"
+        ));
+        assert!(!allows(
+            "https://cdn.test/public/",
+            "https://cdn.test/public/%2e%2e/x"
+        ));
+        assert!(!allows(
+            "https://cdn.test/public",
+            "https://cdn.test/publicity"
+        ));
+        assert!(!allows("https://cdn.test", "https://cdn.test@evil.test/"));
+        assert!(!allows("https://cdn.test", "https://cdn.test.evil.test/"));
+        assert!(!allows("https://cdn.test", "https://user@cdn.test/"));
+        assert!(!allows("https://cdn.test", "http://cdn.test/"));
+        assert!(!allows("https://cdn.test", "https://cdn.test:8443/"));
+    }
+
+    #[cfg(feature = "net-sdk")]
+    #[tokio::test]
+    async fn test_ws_session() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+        use super::{CLOSE_FORBIDDEN, CLOSE_TIMEOUT, CLOSE_UNAUTHORIZED};
+        use crate::{impls::net::Signal, testing::free_port, Satori};
+
+        let port = free_port();
+        let s = DynSatori::builder()
+            .app(NetApp::new(NetAppConfig {
+                port,
+                token: Some("main".to_string()),
+                tokens: vec![NetAppToken {
+                    name: "api".to_string(),
+                    token: "api".to_string(),
+                    bots: None,
+                    methods: vec!["*".to_string()],
+                    events: false,
+                }],
+                ..Default::default()
+            }))
+            .build();
+        s.spawn().await;
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+
+        let url = format!("ws://127.0.0.1:{port}/v1/events");
+        // sends `identify` if any, then returns the close code and the
+        // signals received before it
+        let session = |identify: Option<String>| {
+            let url = url.clone();
+            async move {
+                let (mut ws, _) = connect_async(url).await.unwrap();
+                if let Some(identify) = identify {
+                    ws.send(Message::Text(identify)).await.unwrap();
+                }
+                let mut signals = vec![];
+                while let Some(Ok(message)) = ws.next().await {
+                    match message {
+                        Message::Text(text) => signals.push(text),
+                        Message::Close(frame) => {
+                            return (frame.map(|f| u16::from(f.code)), signals)
+                        }
+                        _ => {}
+                    }
+                }
+                (None, signals)
+            }
+        };
+        let identify = |token: &str| Some(Signal::identify(token, 0).to_string());
+
+        let (code, _) = session(None).await;
+        assert_eq!(code, Some(CLOSE_TIMEOUT));
+        let no_token = r#"{"op":3,"body":{}}"#.to_string();
+        let (code, _) = session(Some(no_token)).await;
+        assert_eq!(code, Some(CLOSE_UNAUTHORIZED));
+        let (code, _) = session(identify("x")).await;
+        assert_eq!(code, Some(CLOSE_FORBIDDEN));
+        let (code, _) = session(identify("api")).await;
+        assert_eq!(code, Some(CLOSE_FORBIDDEN));
+
+        // identified, but silent afterwards
+        let (code, signals) = session(identify("main")).await;
+        assert_eq!(code, Some(CLOSE_TIMEOUT));
+        assert!(matches!(
+            serde_json::from_str(&signals[0]),
+            Ok(Signal::Ready { .. })
+        ));
+
+        s.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_login_events() {
+        let app = NetApp::new(NetAppConfig::default());
+        let mut rx = app.tx.subscribe();
+        let s = DynSatori::builder().build();
+        let bot = BotId {
+            id: "1".to_string(),
+            platform: "test".to_string(),
+        };
+        let login = SystemEvent::BotLogin { bot: bot.clone() }.into_event();
+        let logout = SystemEvent::BotLogout { bot: bot.clone() }.into_event();
+        let added = Event {
+            ty: "login-added".to_string(),
+            platform: "test".to_string(),
+            self_id: "1".to_string(),
+            ..Default::default()
+        };
+        // the platform's own event comes first, routing's is a duplicate
+        for event in [added, login, logout.clone(), logout] {
+            app.handle_event(&s, event).await.unwrap();
+        }
+
+        let mut seen = vec![];
+        while let Ok(event) = rx.try_recv() {
+            seen.push(event.ty);
+        }
+        assert_eq!(seen, vec!["login-added", "login-removed"]);
+    }
+}
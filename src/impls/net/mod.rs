@@ -2,13 +2,100 @@
 pub mod app;
 #[cfg(feature = "net-sdk")]
 pub mod sdk;
+#[cfg(feature = "net-app")]
+mod webhook;
 
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use sha2::Sha256;
 
-use crate::structs::{Event, Login};
+use crate::structs::{BotId, Event, Login};
 
 pub const NET: &str = "Net";
 
+/// Header carrying the HMAC-SHA256 of a webhook delivery, see
+/// [`Webhook::secret`].
+pub const HEADER_SIGNATURE: &str = "x-signature";
+
+/// A receiver of events delivered by HTTP POST, registered through the
+/// `webhook.create` admin API.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Webhook {
+    pub url: String,
+    /// Sent as a bearer token with each delivery.
+    pub token: Option<String>,
+    /// Key for the [`HEADER_SIGNATURE`] of each delivery.
+    pub secret: Option<String>,
+    /// Only deliver events of these bots; all if `None`.
+    #[serde(default)]
+    pub bots: Option<Vec<BotId>>,
+    /// Only deliver events of these types; all if `None`.
+    #[serde(default)]
+    pub events: Option<Vec<String>>,
+    /// Attempts after a failed delivery before giving up.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+impl Webhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            token: None,
+            secret: None,
+            bots: None,
+            events: None,
+            retries: default_retries(),
+        }
+    }
+
+    #[cfg(feature = "net-app")]
+    fn matches(&self, event: &Event) -> bool {
+        let bot = self.bots.as_ref().is_none_or(|bots| {
+            bots.iter()
+                .any(|b| b.platform == event.platform && b.id == event.self_id)
+        });
+        bot && self
+            .events
+            .as_ref()
+            .is_none_or(|tys| tys.contains(&event.ty))
+    }
+}
+
+fn mac(secret: &str, body: &[u8]) -> Hmac<Sha256> {
+    // HMAC takes keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(body);
+    mac
+}
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `body` keyed with `secret`.
+pub fn signature(secret: &str, body: &[u8]) -> String {
+    let mac = mac(secret, body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+/// Check a [`signature`] in constant time.
+pub fn verify_signature(secret: &str, body: &[u8], signature: &str) -> bool {
+    let Some(Ok(expected)) = signature.strip_prefix("sha256=").map(hex::decode) else {
+        return false;
+    };
+    mac(secret, body).verify_slice(&expected).is_ok()
+}
+
+/// Compare secrets in time independent of where they differ.
+fn secret_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 enum Signal {
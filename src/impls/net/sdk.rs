@@ -1,23 +1,47 @@
-use std::{collections::HashSet, net::Ipv4Addr, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 
+use axum::{body::Bytes, extract::State, http::HeaderMap};
 use futures_util::{SinkExt, StreamExt};
 use http::StatusCode;
+use reqwest::{
+    multipart::{Form, Part},
+    Url,
+};
+use rustls::{
+    client::{ServerCertVerified, ServerCertVerifier},
+    Certificate, ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName,
+};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{json, Value};
 use tokio::{sync::RwLock, time::Instant};
-use tokio_tungstenite::connect_async;
-use tracing::{error, info, trace};
+use tokio_tungstenite::{connect_async_tls_with_config, Connector, MaybeTlsStream};
+use tracing::{debug, error, info, trace, warn};
 
-use super::{Logins, Signal};
+use super::{secret_eq, verify_signature, Logins, Signal, Webhook, HEADER_SIGNATURE};
 use crate::{
     api::RawApiCall,
     error::{ApiError, MapSatoriError, SatoriError},
     impls::net::NET,
-    structs::{BotId, Login, Status},
+    runtime::LifecycleState,
+    structs::{BotId, Event, Login},
+    system::SystemEvent,
+    upload::Upload,
     Satori, SatoriSDK,
 };
 
 type WsMessage = tokio_tungstenite::tungstenite::Message;
+type WsStream = tokio_tungstenite::WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>;
+
+const RECONNECT_DELAY: Duration = Duration::from_millis(500);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(30);
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct NetSDKConfig {
@@ -25,6 +49,19 @@ pub struct NetSDKConfig {
     pub port: u16,
     pub path: Option<String>,
     pub token: Option<String>,
+    /// Base URL of the server, e.g. `https://example.com:5140/satori`;
+    /// replaces `host`, `port` and `path`.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// PEM file of additional CA certificates to trust.
+    #[serde(default)]
+    pub ca_cert: Option<PathBuf>,
+    /// Accept any server certificate. Only for testing.
+    #[serde(default)]
+    pub insecure: bool,
+    /// Receive events by webhook instead of WebSocket.
+    #[serde(default)]
+    pub webhook: Option<NetSDKWebhook>,
 }
 
 impl Default for NetSDKConfig {
@@ -34,6 +71,84 @@ impl Default for NetSDKConfig {
             port: 5140,
             path: None,
             token: None,
+            url: None,
+            ca_cert: None,
+            insecure: false,
+            webhook: None,
+        }
+    }
+}
+
+/// A local endpoint the server posts events to. It is registered with
+/// `webhook.create` on start and removed with `webhook.delete` on shutdown.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NetSDKWebhook {
+    pub host: IpAddr,
+    pub port: u16,
+    pub path: String,
+    /// Where the server reaches this endpoint, e.g. through a load balancer.
+    pub url: String,
+    /// Required from the server as a bearer token.
+    pub token: Option<String>,
+    /// Required to sign each delivery, see [`Webhook::secret`].
+    pub secret: Option<String>,
+}
+
+/// Logins of the server's bots, updated from `login-*` events.
+pub type LoginMap = HashMap<BotId, Login>;
+
+/// Key `logins` by bot, skipping those without ids.
+fn login_map(logins: Vec<Login>) -> LoginMap {
+    logins
+        .into_iter()
+        .filter_map(|login| {
+            let (Some(platform), Some(id)) = (&login.platform, &login.self_id) else {
+                warn!(target: NET, ?login, "login without id ignored");
+                return None;
+            };
+            let bot = BotId {
+                id: id.clone(),
+                platform: platform.clone(),
+            };
+            Some((bot, login))
+        })
+        .collect()
+}
+
+/// Apply `event` to `logins` if it reports a login change.
+async fn track_login(logins: &RwLock<LoginMap>, event: &Event) {
+    let bot = BotId {
+        id: event.self_id.clone(),
+        platform: event.platform.clone(),
+    };
+    match event.ty.as_str() {
+        "login-added" | "login-updated" => {
+            let mut login = event.login.clone().unwrap_or_default();
+            login.platform.get_or_insert_with(|| bot.platform.clone());
+            login.self_id.get_or_insert_with(|| bot.id.clone());
+            debug!(target: NET, ?bot, status = ?login.status, "{}", event.ty);
+            logins.write().await.insert(bot, login);
+        }
+        "login-removed" => {
+            debug!(target: NET, ?bot, "login-removed");
+            logins.write().await.remove(&bot);
+        }
+        _ => {}
+    }
+}
+
+struct HookState<S> {
+    s: Arc<S>,
+    logins: Arc<RwLock<LoginMap>>,
+    config: Arc<NetSDKWebhook>,
+}
+
+impl<S> Clone for HookState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            s: self.s.clone(),
+            logins: self.logins.clone(),
+            config: self.config.clone(),
         }
     }
 }
@@ -41,48 +156,351 @@ impl Default for NetSDKConfig {
 #[derive(Debug)]
 pub struct NetSDK {
     config: NetSDKConfig,
-    pub bots: Arc<RwLock<HashSet<BotId>>>,
+    pub logins: Arc<RwLock<LoginMap>>,
     client: reqwest::Client,
+    tls: Arc<ClientConfig>,
+    /// Bots the server answered with `Retry-After`, until when.
+    backoff: std::sync::Mutex<HashMap<BotId, Instant>>,
 }
 
 impl NetSDK {
     pub fn new(config: NetSDKConfig) -> Self {
+        let tls = Arc::new(tls_config(&config));
+        let client = reqwest::Client::builder()
+            .use_preconfigured_tls((*tls).clone())
+            .build()
+            .unwrap();
         Self {
             config,
-            bots: Default::default(),
-            client: reqwest::Client::new(),
+            logins: Default::default(),
+            backoff: Default::default(),
+            client,
+            tls,
         }
     }
 }
 
-impl SatoriSDK for NetSDK {
-    #[allow(unused_assignments)]
-    // TODO: seq
-    async fn start<S>(&self, s: &Arc<S>)
+fn tls_config(config: &NetSDKConfig) -> ClientConfig {
+    let mut roots = RootCertStore::empty();
+    roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|ta| {
+        OwnedTrustAnchor::from_subject_spki_name_constraints(
+            ta.subject,
+            ta.spki,
+            ta.name_constraints,
+        )
+    }));
+    if let Some(path) = &config.ca_cert {
+        let certs = File::open(path).and_then(|f| rustls_pemfile::certs(&mut BufReader::new(f)));
+        match certs {
+            Ok(certs) => {
+                roots.add_parsable_certificates(&certs);
+            }
+            Err(e) => {
+                error!(target: NET, path = %path.display(), "failed to read CA certificates: {e}")
+            }
+        }
+    }
+    let mut tls = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    if config.insecure {
+        warn!(target: NET, "server certificates are not verified");
+        tls.dangerous()
+            .set_certificate_verifier(Arc::new(AcceptAnyCert));
+    }
+    tls
+}
+
+struct AcceptAnyCert;
+
+impl ServerCertVerifier for AcceptAnyCert {
+    fn verify_server_cert(
+        &self,
+        _end_entity: &Certificate,
+        _intermediates: &[Certificate],
+        _server_name: &ServerName,
+        _scts: &mut dyn Iterator<Item = &[u8]>,
+        _ocsp_response: &[u8],
+        _now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+impl NetSDK {
+    /// Bots of the server.
+    #[deprecated = "use `logins`, which also keeps each bot's login"]
+    pub async fn bots(&self) -> HashSet<BotId> {
+        self.logins.read().await.keys().cloned().collect()
+    }
+
+    fn base_url(&self) -> String {
+        match &self.config.url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => format!(
+                "http://{}:{}{}",
+                self.config.host,
+                self.config.port,
+                self.config.path.as_deref().unwrap_or_default(),
+            ),
+        }
+    }
+
+    fn http_url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url())
+    }
+
+    async fn post(&self, bot: &BotId, path: &str, body: &Value) -> Result<Value, SatoriError> {
+        if !self.logins.read().await.contains_key(bot) {
+            return Err(SatoriError::InvalidBot);
+        }
+        let until = self.backoff.lock().unwrap().get(bot).copied();
+        if let Some(until) = until {
+            tokio::time::sleep_until(until).await;
+        }
+
+        let mut req = self
+            .client
+            .post(self.http_url(path))
+            .header("X-Platform", &bot.platform)
+            .header("X-Self-ID", &bot.id)
+            .json(body);
+        if let Some(token) = &self.config.token {
+            req = req.bearer_auth(token);
+        }
+        trace!(target: NET, ?req);
+
+        let resp = req.send().await.map_internal_error()?;
+        trace!(target: NET, ?resp);
+
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await.map_internal_error()?),
+            _ => {
+                let e = ApiError::from_respponse(resp).await?;
+                let mut backoff = self.backoff.lock().unwrap();
+                backoff.retain(|_, until| *until > Instant::now());
+                if let ApiError::RateLimited(Some(delay)) = &e {
+                    backoff.insert(bot.clone(), Instant::now() + *delay);
+                }
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Upload files with `upload.create`, returning URLs by field name.
+    pub async fn create_upload(
+        &self,
+        bot: &BotId,
+        files: Vec<(String, Upload)>,
+    ) -> Result<HashMap<String, String>, SatoriError> {
+        if !self.logins.read().await.contains_key(bot) {
+            return Err(SatoriError::InvalidBot);
+        }
+        let mut form = Form::new();
+        for (name, file) in files {
+            let mut part = Part::bytes(file.data);
+            if let Some(filename) = file.filename {
+                part = part.file_name(filename);
+            }
+            if let Some(mime) = file.mime {
+                part = part.mime_str(&mime).map_internal_error()?;
+            }
+            form = form.part(name, part);
+        }
+        let mut req = self
+            .client
+            .post(self.http_url("/v1/upload.create"))
+            .header("X-Platform", &bot.platform)
+            .header("X-Self-ID", &bot.id)
+            .multipart(form);
+        if let Some(token) = &self.config.token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().await.map_internal_error()?;
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await.map_internal_error()?),
+            _ => Err(SatoriError::ApiError(ApiError::from_respponse(resp).await?)),
+        }
+    }
+
+    /// Fetch a platform resource through the server's proxy route.
+    pub async fn proxy(&self, url: &str) -> Result<Vec<u8>, SatoriError> {
+        let mut req = self.client.get(self.http_url(&format!("/v1/proxy/{url}")));
+        if let Some(token) = &self.config.token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().await.map_internal_error()?;
+        match resp.status() {
+            StatusCode::OK => Ok(resp.bytes().await.map_internal_error()?.to_vec()),
+            _ => Err(SatoriError::ApiError(ApiError::from_respponse(resp).await?)),
+        }
+    }
+
+    async fn admin(&self, method: &str, body: Value) -> Result<(), SatoriError> {
+        let mut req = self
+            .client
+            .post(self.http_url(&format!("/v1/admin/{method}")))
+            .json(&body);
+        if let Some(token) = &self.config.token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().await.map_internal_error()?;
+        match resp.status() {
+            StatusCode::OK => Ok(()),
+            _ => Err(SatoriError::ApiError(ApiError::from_respponse(resp).await?)),
+        }
+    }
+
+    /// Fetch the server's bots with `login.list`.
+    async fn list_logins(&self) -> Result<Vec<Login>, SatoriError> {
+        // the call isn't made as any bot, but the headers are required
+        let mut req = self
+            .client
+            .post(self.http_url("/v1/login.list"))
+            .header("X-Platform", "")
+            .header("X-Self-ID", "")
+            .json(&json!({}));
+        if let Some(token) = &self.config.token {
+            req = req.bearer_auth(token);
+        }
+        let resp = req.send().await.map_internal_error()?;
+        match resp.status() {
+            StatusCode::OK => Ok(resp.json().await.map_internal_error()?),
+            _ => Err(SatoriError::ApiError(ApiError::from_respponse(resp).await?)),
+        }
+    }
+
+    async fn run_webhook<S>(&self, s: &Arc<S>, config: &NetSDKWebhook)
     where
         S: Satori + Send + Sync + 'static,
     {
-        let s = s.clone();
-        let bots = self.bots.clone();
-
-        let addr = format!(
-            "ws://{}:{}{}/v1/events",
-            self.config.host,
-            self.config.port,
-            self.config.path.as_deref().unwrap_or_default()
-        );
-        let (mut ws_stream, _) = connect_async(&addr).await.unwrap();
-        info!(target: NET, "WebSocket connected with {addr}");
+        let app = axum::Router::new()
+            .route(&config.path, axum::routing::post(receive::<S>))
+            .with_state(HookState {
+                s: s.clone(),
+                logins: self.logins.clone(),
+                config: Arc::new(config.clone()),
+            });
+        let server = match axum::Server::try_bind(&SocketAddr::from((config.host, config.port))) {
+            Ok(server) => server
+                .serve(app.into_make_service())
+                .with_graceful_shutdown(s.stopped()),
+            Err(e) => {
+                error!(target: NET, "failed to bind webhook endpoint: {e}");
+                return;
+            }
+        };
+        let webhook = Webhook {
+            token: config.token.clone(),
+            secret: config.secret.clone(),
+            ..Webhook::new(&config.url)
+        };
+        if let Err(e) = self.admin("webhook.create", json!(webhook)).await {
+            error!(target: NET, url = config.url, "failed to register webhook: {e}");
+            return;
+        }
+        info!(target: NET, "Webhook registered as {}", config.url);
+        match self.list_logins().await {
+            Ok(logins) => self.logins.write().await.extend(login_map(logins)),
+            // bots are still learned from their events
+            Err(e) => warn!(target: NET, "failed to list logins: {e}"),
+        }
+        s.runtime().emit(SystemEvent::SdkConnected {
+            sdk: config.url.clone(),
+        });
 
-        let mut seq = 0i64;
-        ws_stream
-            .send(
-                Signal::identify(&self.config.token.clone().unwrap_or_default(), seq)
-                    .to_string()
-                    .into(),
-            )
+        if let Err(e) = server.await {
+            error!(target: NET, "webhook endpoint error: {e}");
+        }
+
+        if let Err(e) = self
+            .admin("webhook.delete", json!({ "url": config.url }))
             .await
-            .unwrap();
+        {
+            warn!(target: NET, url = config.url, "failed to unregister webhook: {e}");
+        }
+        s.runtime().emit(SystemEvent::SdkDisconnected {
+            sdk: config.url.clone(),
+        });
+    }
+
+    /// The `/v1/events` URL, on `ws` for `http` servers and `wss` for
+    /// `https` ones.
+    fn ws_url(&self) -> Result<Url, SatoriError> {
+        let mut url = Url::parse(&self.http_url("/v1/events")).map_internal_error()?;
+        let scheme = match url.scheme() {
+            "http" | "ws" => "ws",
+            "https" | "wss" => "wss",
+            scheme => return Err(anyhow::anyhow!("unsupported scheme {scheme}").into()),
+        };
+        // switching between these special schemes always succeeds
+        url.set_scheme(scheme).unwrap();
+        Ok(url)
+    }
+
+    /// Keep a WebSocket session with the server, reconnecting with backoff.
+    async fn run_ws<S>(&self, s: &Arc<S>)
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let addr = match self.ws_url() {
+            Ok(url) => url.to_string(),
+            Err(e) => {
+                error!(target: NET, "invalid server url: {e}");
+                return;
+            }
+        };
+        let connector = Connector::Rustls(self.tls.clone());
+        let mut seq = 0i64;
+        let mut delay = RECONNECT_DELAY;
+        loop {
+            let connect =
+                connect_async_tls_with_config(&addr, None, false, Some(connector.clone()));
+            let connected = tokio::select! {
+                connected = connect => connected,
+                _ = s.stopped() => return,
+            };
+            match connected {
+                Ok((ws_stream, _)) => {
+                    info!(target: NET, "WebSocket connected with {addr}");
+                    s.runtime()
+                        .emit(SystemEvent::SdkConnected { sdk: addr.clone() });
+                    if self.ws_session(s, ws_stream, &mut seq).await {
+                        delay = RECONNECT_DELAY;
+                    }
+                    s.runtime()
+                        .emit(SystemEvent::SdkDisconnected { sdk: addr.clone() });
+                }
+                Err(e) => warn!(target: NET, "failed to connect to {addr}: {e}"),
+            }
+            if s.runtime().state() >= LifecycleState::Stopping {
+                return;
+            }
+            debug!(target: NET, ?delay, "reconnecting to {addr}");
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = s.stopped() => return,
+            }
+            delay = (delay * 2).min(MAX_RECONNECT_DELAY);
+        }
+    }
+
+    /// Identify on `ws_stream` and handle its events until either side
+    /// closes it, returning whether the server got ready. `seq` is the id of
+    /// the last event received.
+    async fn ws_session<S>(&self, s: &Arc<S>, mut ws_stream: WsStream, seq: &mut i64) -> bool
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let logins = &self.logins;
+        let mut ready = false;
+        let token = self.config.token.clone().unwrap_or_default();
+        let identify = Signal::identify(&token, *seq).to_string();
+        if let Err(e) = ws_stream.send(identify.into()).await {
+            warn!(target: NET, "failed to identify: {e}");
+            return ready;
+        }
         let mut interval = tokio::time::interval_at(
             Instant::now() + Duration::from_secs(10),
             Duration::from_secs(10),
@@ -90,7 +508,10 @@ impl SatoriSDK for NetSDK {
         loop {
             tokio::select! {
                 _ = interval.tick() => {
-                    ws_stream.send(Signal::ping().to_string().into()).await.unwrap();
+                    if let Err(e) = ws_stream.send(Signal::ping().to_string().into()).await {
+                        warn!(target: NET, "failed to ping: {e}");
+                        break;
+                    }
                 }
                 data = ws_stream.next() => {
                     trace!(target: NET, "receive ws_msg: {:?}" ,data);
@@ -99,21 +520,16 @@ impl SatoriSDK for NetSDK {
                             Ok(signal) => match signal {
                                 Signal::Event { body: event, .. } => {
                                     info!(target: NET, "receive event: {:?}", event);
-                                    // TODO: seq
-                                    seq = event.id;
-                                    s.handle_event(event);
+                                    *seq = event.id;
+                                    track_login(logins, &event).await;
+                                    s.handle_event(event).await;
                                 }
                                 Signal::Pong { .. } => {}
-                                Signal::Ready { body: Logins { logins }, .. } => {
-                                    let mut bots = bots.write().await;
-                                    for login in logins {
-                                        bots.insert(BotId {
-                                            platform: login.platform.unwrap(),
-                                            id: login.self_id.unwrap(),
-                                        });
-                                    }
+                                Signal::Ready { body: Logins { logins: list }, .. } => {
+                                    *logins.write().await = login_map(list);
+                                    ready = true;
                                 }
-                                _ => unreachable!(),
+                                signal => warn!(target: NET, ?signal, "unexpected signal"),
                             },
                             Err(e) =>  error!(target: NET, "deserialize error: {e} in {text}"),
                         }
@@ -131,6 +547,76 @@ impl SatoriSDK for NetSDK {
                 }
             }
         }
+        ready
+    }
+}
+
+async fn receive<S>(
+    State(state): State<HookState<S>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> StatusCode
+where
+    S: Satori + Send + Sync + 'static,
+{
+    let config = &state.config;
+    if let Some(token) = &config.token {
+        let presented = headers
+            .get(http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        if !presented.is_some_and(|presented| secret_eq(presented, token)) {
+            return StatusCode::UNAUTHORIZED;
+        }
+    }
+    if let Some(secret) = &config.secret {
+        let signed = headers
+            .get(HEADER_SIGNATURE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|sig| verify_signature(secret, &body, sig));
+        if !signed {
+            return StatusCode::FORBIDDEN;
+        }
+    }
+    let event: Event = match serde_json::from_slice(&body) {
+        Ok(event) => event,
+        Err(e) => {
+            error!(target: NET, "deserialize error: {e}");
+            return StatusCode::BAD_REQUEST;
+        }
+    };
+    track_login(&state.logins, &event).await;
+    // the server only posts events of bots it has, so learn them here
+    if event.ty != "login-removed" {
+        let bot = BotId {
+            id: event.self_id.clone(),
+            platform: event.platform.clone(),
+        };
+        state
+            .logins
+            .write()
+            .await
+            .entry(bot)
+            .or_insert_with(|| Login {
+                self_id: Some(event.self_id.clone()),
+                platform: Some(event.platform.clone()),
+                ..Default::default()
+            });
+    }
+    info!(target: NET, "receive event: {:?}", event);
+    state.s.handle_event(event).await;
+    StatusCode::OK
+}
+
+impl SatoriSDK for NetSDK {
+    async fn start<S>(&self, s: &Arc<S>)
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        match &self.config.webhook {
+            Some(webhook) => self.run_webhook(s, webhook).await,
+            None => self.run_ws(s).await,
+        }
     }
 
     async fn call_api<S>(
@@ -142,51 +628,550 @@ impl SatoriSDK for NetSDK {
     where
         S: Satori + Send + Sync + 'static,
     {
-        if !self.bots.read().await.contains(bot) {
-            return Err(SatoriError::InvalidBot);
+        self.post(bot, &format!("/v1/{}", payload.method), &payload.body)
+            .await
+    }
+
+    async fn call_internal<S>(
+        &self,
+        _s: &Arc<S>,
+        bot: &BotId,
+        payload: RawApiCall,
+    ) -> Result<Value, SatoriError>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let path = format!("/v1/internal/{}", payload.method);
+        self.post(bot, &path, &payload.body).await
+    }
+
+    async fn upload<S>(&self, _s: &Arc<S>, bot: &BotId, file: Upload) -> Result<String, SatoriError>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let result = self
+            .create_upload(bot, vec![("file".to_string(), file)])
+            .await;
+        let mut urls = match result {
+            // not `NotFound`: a locally stored file would get a URL the server
+            // can't fetch
+            Err(SatoriError::ApiError(ApiError::NotFound)) => {
+                return Err(anyhow::anyhow!("server does not support upload.create").into())
+            }
+            result => result?,
+        };
+        urls.remove("file")
+            .ok_or_else(|| anyhow::anyhow!("no url for uploaded file").into())
+    }
+
+    async fn has_bot(&self, bot: &BotId) -> bool {
+        self.logins.read().await.contains_key(bot)
+    }
+
+    async fn get_logins(&self) -> Vec<Login> {
+        self.logins.read().await.values().cloned().collect()
+    }
+}
+
+#[cfg(all(test, feature = "net-app"))]
+mod tests {
+    use std::{
+        net::{IpAddr, Ipv4Addr, SocketAddr},
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use serde_json::{json, Value};
+
+    use super::{login_map, track_login, NetSDK, NetSDKConfig, NetSDKWebhook};
+    use crate::{
+        api::RawApiCall,
+        dynamic::DynSatori,
+        error::{ApiError, SatoriError},
+        impls::net::{
+            app::{NetApp, NetAppConfig, NetAppTls},
+            signature, HEADER_SIGNATURE,
+        },
+        structs::{BotId, Event, Login, Status},
+        system::is_internal,
+        testing::{bot, free_port},
+        upload::Upload,
+        Satori, SatoriApp, SatoriSDK,
+    };
+
+    /// Answers internal calls with their method and allows proxying `prefix`.
+    struct Platform {
+        prefix: String,
+        logins: Vec<Login>,
+    }
+
+    impl SatoriSDK for Platform {
+        async fn start<S>(&self, s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            s.stopped().await
         }
 
-        let mut req = self
-            .client
-            .post(format!(
-                "http://{}:{}{}/v1/{}",
-                self.config.host,
-                self.config.port,
-                self.config.path.as_deref().unwrap_or_default(),
-                payload.method
-            ))
-            .header("X-Platform", &bot.platform)
-            .header("X-Self-ID", &bot.id)
-            .json(&payload.body);
-        if let Some(token) = &self.config.token {
-            req = req.bearer_auth(token);
+        async fn call_api<S>(
+            &self,
+            _s: &Arc<S>,
+            _bot: &BotId,
+            _payload: RawApiCall,
+        ) -> Result<Value, SatoriError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            Err(ApiError::NotFound.into())
         }
-        trace!(target: NET, ?req);
 
-        let resp = req.send().await.unwrap();
-        trace!(target: NET, ?resp);
+        async fn call_internal<S>(
+            &self,
+            _s: &Arc<S>,
+            _bot: &BotId,
+            payload: RawApiCall,
+        ) -> Result<Value, SatoriError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            Ok(json!({ "method": payload.method, "body": payload.body }))
+        }
 
-        match resp.status() {
-            StatusCode::OK => Ok(resp.json().await.map_internal_error()?),
-            _ => Err(SatoriError::ApiError(ApiError::from_respponse(resp).await?)),
+        async fn has_bot(&self, _bot: &BotId) -> bool {
+            true
+        }
+
+        async fn get_logins(&self) -> Vec<Login> {
+            self.logins.clone()
+        }
+
+        fn proxy_urls(&self) -> Vec<String> {
+            vec![self.prefix.clone()]
         }
     }
 
-    async fn has_bot(&self, bot: &BotId) -> bool {
-        self.bots.read().await.contains(bot)
+    #[tokio::test]
+    async fn test_internal_and_proxy() {
+        let files = axum::Router::new()
+            .route("/public/a", axum::routing::get(|| async { "file a" }))
+            .route("/private/b", axum::routing::get(|| async { "file b" }));
+        let files = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(files.into_make_service());
+        let origin = format!("http://{}", files.local_addr());
+        tokio::spawn(files);
+
+        let port = free_port();
+        let server = DynSatori::builder()
+            .sdk(Platform {
+                prefix: format!("{origin}/public/"),
+                logins: vec![],
+            })
+            .app(NetApp::new(NetAppConfig {
+                port,
+                ..Default::default()
+            }))
+            .build();
+        server.spawn().await;
+
+        let client = DynSatori::builder().build();
+        let sdk = NetSDK::new(NetSDKConfig {
+            port,
+            ..Default::default()
+        });
+        let bot = BotId {
+            id: "1".to_string(),
+            platform: "test".to_string(),
+        };
+        sdk.logins
+            .write()
+            .await
+            .insert(bot.clone(), Login::default());
+        let call = || {
+            let payload = RawApiCall {
+                method: "group/info".to_string(),
+                body: json!({ "id": 1 }),
+            };
+            sdk.call_internal(&client, &bot, payload)
+        };
+        let mut result = call().await;
+        for _ in 0..50 {
+            if !matches!(result, Err(SatoriError::InternalError(_))) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            result = call().await;
+        }
+        assert_eq!(
+            result.unwrap(),
+            json!({ "method": "group/info", "body": { "id": 1 } })
+        );
+
+        assert_eq!(
+            sdk.proxy(&format!("{origin}/public/a")).await.unwrap(),
+            b"file a"
+        );
+        for url in [
+            format!("{origin}/private/b"),
+            // decoded once by the route, so this reaches the check as `%2e%2e`
+            format!("{origin}/public/%252e%252e/private/b"),
+        ] {
+            assert!(matches!(
+                sdk.proxy(&url).await,
+                Err(SatoriError::ApiError(ApiError::Forbidden))
+            ));
+        }
+
+        // the mock platform can't upload, so the server keeps the file
+        let url = sdk
+            .upload(&client, &bot, Upload::new("file c").filename("c.txt"))
+            .await
+            .unwrap();
+        assert!(url.starts_with("internal:test/1/upload/"));
+        assert_eq!(sdk.proxy(&url).await.unwrap(), b"file c");
+
+        server.shutdown().await;
     }
 
-    async fn get_logins(&self) -> Vec<Login> {
-        self.bots
-            .read()
+    #[tokio::test]
+    async fn test_upload_unsupported() {
+        // a server without any routes
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(axum::Router::new().into_make_service());
+        let port = server.local_addr().port();
+        tokio::spawn(server);
+
+        let sdk = NetSDK::new(NetSDKConfig {
+            port,
+            ..Default::default()
+        });
+        sdk.logins.write().await.insert(bot(), Login::default());
+        let client = DynSatori::builder().sdk(sdk).build();
+        let result = client.upload(&bot(), Upload::new("file")).await;
+        assert!(matches!(result, Err(SatoriError::InternalError(_))));
+    }
+
+    /// Records the type of every event it sees, but internal ones.
+    struct Seen(Arc<Mutex<Vec<String>>>);
+
+    impl SatoriApp for Seen {
+        type Error = SatoriError;
+
+        async fn start<S>(&self, _s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+        }
+
+        async fn handle_event<S>(&self, _s: &Arc<S>, event: Event) -> Result<(), SatoriError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            if !is_internal(&event) {
+                self.0.lock().unwrap().push(event.ty);
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook() {
+        let port = free_port();
+        let server = DynSatori::builder()
+            .sdk(Platform {
+                prefix: String::new(),
+                logins: vec![Login {
+                    self_id: Some("1".to_string()),
+                    platform: Some("test".to_string()),
+                    ..Default::default()
+                }],
+            })
+            .app(NetApp::new(NetAppConfig {
+                port,
+                ..Default::default()
+            }))
+            .build();
+        server.spawn().await;
+
+        let hook_port = free_port();
+        let url = format!("http://127.0.0.1:{hook_port}/hook");
+        let seen = Arc::new(Mutex::new(vec![]));
+        let client = DynSatori::builder().app(Seen(seen.clone())).build();
+        let sdk = Arc::new(NetSDK::new(NetSDKConfig {
+            port,
+            webhook: Some(NetSDKWebhook {
+                host: IpAddr::V4(Ipv4Addr::LOCALHOST),
+                port: hook_port,
+                path: "/hook".to_string(),
+                url: url.clone(),
+                token: Some("token".to_string()),
+                secret: Some("secret".to_string()),
+            }),
+            ..Default::default()
+        }));
+        client.spawn().await;
+        tokio::spawn({
+            let (sdk, client) = (sdk.clone(), client.clone());
+            async move { sdk.start(&client).await }
+        });
+
+        // registered, and the server's bots are known before any event
+        let bot = bot();
+        for _ in 0..50 {
+            if sdk.has_bot(&bot).await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(sdk.has_bot(&bot).await);
+
+        server
+            .handle_event(Event {
+                ty: "guild-added".to_string(),
+                platform: "test".to_string(),
+                self_id: "1".to_string(),
+                ..Default::default()
+            })
+            .await;
+        let delivered = || seen.lock().unwrap().contains(&"guild-added".to_string());
+        for _ in 0..50 {
+            if delivered() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(delivered());
+
+        let body = json!({ "id": 1, "type": "guild-added", "platform": "test", "self_id": "1", "timestamp": 0 }).to_string();
+        let post = |token: &str, signature: String| {
+            reqwest::Client::new()
+                .post(&url)
+                .bearer_auth(token)
+                .header(HEADER_SIGNATURE, signature)
+                .body(body.clone())
+                .send()
+        };
+        let status = |resp: reqwest::Result<reqwest::Response>| resp.unwrap().status();
+        let signed = signature("secret", body.as_bytes());
+        assert_eq!(status(post("wrong", signed.clone()).await), 401);
+        assert_eq!(
+            status(post("token", signature("wrong", body.as_bytes())).await),
+            403
+        );
+        assert_eq!(status(post("token", signed).await), 200);
+
+        server.shutdown().await;
+        client.shutdown().await;
+    }
+
+    #[test]
+    fn test_ws_url() {
+        let ws_url = |url: &str| {
+            let sdk = NetSDK::new(NetSDKConfig {
+                url: Some(url.to_string()),
+                ..Default::default()
+            });
+            sdk.ws_url().map(|url| url.to_string()).ok()
+        };
+        for (url, expected) in [
+            ("http://a:1/x/", "ws://a:1/x/v1/events"),
+            ("https://a/x", "wss://a/x/v1/events"),
+            ("ws://a:1", "ws://a:1/v1/events"),
+            ("wss://a", "wss://a/v1/events"),
+        ] {
+            assert_eq!(ws_url(url).as_deref(), Some(expected));
+        }
+        assert_eq!(ws_url("ftp://a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_ws_reconnect() {
+        let port = free_port();
+        let client = DynSatori::builder().build();
+        let sdk = Arc::new(NetSDK::new(NetSDKConfig {
+            port,
+            ..Default::default()
+        }));
+        client.spawn().await;
+        tokio::spawn({
+            let (sdk, client) = (sdk.clone(), client.clone());
+            async move { sdk.start(&client).await }
+        });
+        // the first attempt fails, nothing listens yet
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let server = DynSatori::builder()
+            .sdk(Platform {
+                prefix: String::new(),
+                logins: vec![Login {
+                    self_id: Some("1".to_string()),
+                    platform: Some("test".to_string()),
+                    ..Default::default()
+                }],
+            })
+            .app(NetApp::new(NetAppConfig {
+                port,
+                ..Default::default()
+            }))
+            .build();
+        server.spawn().await;
+        let bot = bot();
+        for _ in 0..50 {
+            if sdk.has_bot(&bot).await {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert!(sdk.has_bot(&bot).await);
+
+        server.shutdown().await;
+        client.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_logins() {
+        let sdk = NetSDK::new(NetSDKConfig::default());
+        let login = |id: Option<&str>, status| Login {
+            self_id: id.map(ToString::to_string),
+            platform: Some("test".to_string()),
+            status,
+            ..Default::default()
+        };
+        *sdk.logins.write().await = login_map(vec![
+            login(Some("1"), Status::Online),
+            login(None, Status::Online),
+        ]);
+        let event = |ty: &str, id: &str, login| Event {
+            ty: ty.to_string(),
+            platform: "test".to_string(),
+            self_id: id.to_string(),
+            login,
+            ..Default::default()
+        };
+        for event in [
+            event("login-added", "2", None),
+            event("login-updated", "1", Some(login(None, Status::Offline))),
+            event("login-removed", "2", None),
+        ] {
+            track_login(&sdk.logins, &event).await;
+        }
+
+        let logins = sdk.get_logins().await;
+        assert_eq!(logins.len(), 1);
+        assert_eq!(logins[0].self_id.as_deref(), Some("1"));
+        assert!(matches!(logins[0].status, Status::Offline));
+        let bot = |id: &str| BotId {
+            id: id.to_string(),
+            platform: "test".to_string(),
+        };
+        assert!(sdk.has_bot(&bot("1")).await);
+        assert!(!sdk.has_bot(&bot("2")).await);
+        #[allow(deprecated)]
+        let bots = sdk.bots().await;
+        assert_eq!(bots, [bot("1")].into());
+    }
+
+    #[tokio::test]
+    async fn test_ws_unexpected_signals() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio::net::TcpListener;
+
+        use crate::impls::net::Signal;
+
+        // a server answering identify with identify, then closing
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            for _ in 0..2 {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+                ws.next().await.unwrap().unwrap();
+                let identify = Signal::identify("", 0).to_string();
+                ws.send(identify.into()).await.unwrap();
+                ws.close(None).await.unwrap();
+            }
+        });
+
+        let client = DynSatori::builder().build();
+        let sdk = Arc::new(NetSDK::new(NetSDKConfig {
+            port,
+            ..Default::default()
+        }));
+        client.spawn().await;
+        let task = tokio::spawn({
+            let (sdk, client) = (sdk.clone(), client.clone());
+            async move { sdk.start(&client).await }
+        });
+        // the client survives and comes back
+        tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!task.is_finished());
+
+        client.shutdown().await;
+        tokio::time::timeout(Duration::from_secs(1), task)
             .await
-            .iter()
-            .map(|bot| Login {
-                user: None,
-                self_id: Some(bot.id.clone()),
-                platform: Some(bot.platform.clone()),
-                status: Status::Online,
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_tls() {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("satori-tls-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let tls = NetAppTls {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+        };
+        std::fs::write(&tls.cert, cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(&tls.key, cert.serialize_private_key_pem()).unwrap();
+
+        let port = free_port();
+        let s = DynSatori::builder()
+            .app(NetApp::new(NetAppConfig {
+                port,
+                tls: Some(tls.clone()),
+                ..Default::default()
+            }))
+            .build();
+        s.spawn().await;
+
+        let sdk = |ca_cert, insecure| {
+            NetSDK::new(NetSDKConfig {
+                url: Some(format!("https://localhost:{port}/")),
+                ca_cert,
+                insecure,
+                ..Default::default()
             })
-            .collect()
+        };
+        // any answer from the server proves the TLS handshake passed
+        let call = |sdk: NetSDK| async move {
+            sdk.admin("webhook.delete", json!({ "url": "none" })).await
+        };
+        let trusted = sdk(Some(tls.cert.clone()), false);
+        let mut result = call(trusted).await;
+        for _ in 0..50 {
+            if !matches!(result, Err(SatoriError::InternalError(_))) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            result = call(sdk(Some(tls.cert.clone()), false)).await;
+        }
+        assert!(matches!(
+            result,
+            Err(SatoriError::ApiError(ApiError::NotFound))
+        ));
+        assert!(matches!(
+            call(sdk(None, true)).await,
+            Err(SatoriError::ApiError(ApiError::NotFound))
+        ));
+        assert!(matches!(
+            call(sdk(None, false)).await,
+            Err(SatoriError::InternalError(_))
+        ));
+
+        s.shutdown().await;
+        std::fs::remove_dir_all(dir).ok();
     }
 }
@@ -0,0 +1,213 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::{sync::mpsc, task::AbortHandle};
+use tracing::{debug, warn};
+
+use super::{signature, Webhook, HEADER_SIGNATURE, NET};
+use crate::{structs::Event, Satori};
+
+/// Events waiting for delivery per webhook; further events are dropped.
+const QUEUE_SIZE: usize = 256;
+const RETRY_DELAY: Duration = Duration::from_millis(500);
+
+struct Sink {
+    webhook: Arc<Webhook>,
+    tx: mpsc::Sender<Event>,
+    worker: AbortHandle,
+}
+
+/// Registered webhooks, each delivering from its own queue.
+#[derive(Default)]
+pub(super) struct Webhooks {
+    client: reqwest::Client,
+    sinks: Mutex<HashMap<String, Sink>>,
+}
+
+impl std::fmt::Debug for Webhooks {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sinks = self.sinks.lock().unwrap();
+        f.debug_list()
+            .entries(sinks.values().map(|s| &s.webhook))
+            .finish()
+    }
+}
+
+impl Webhooks {
+    /// Register `webhook`, replacing any with the same url.
+    pub fn add<S>(&self, s: &Arc<S>, webhook: Webhook)
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        let webhook = Arc::new(webhook);
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        let worker = s.runtime().spawn(
+            format!("webhook {}", webhook.url),
+            run(s.clone(), self.client.clone(), webhook.clone(), rx),
+        );
+        debug!(target: NET, url = webhook.url, "webhook added");
+        let sink = Sink {
+            webhook: webhook.clone(),
+            tx,
+            worker,
+        };
+        let old = self.sinks.lock().unwrap().insert(webhook.url.clone(), sink);
+        if let Some(old) = old {
+            old.worker.abort();
+        }
+    }
+
+    /// Unregister the webhook for `url`, dropping its pending events.
+    pub fn remove(&self, url: &str) -> bool {
+        let Some(sink) = self.sinks.lock().unwrap().remove(url) else {
+            return false;
+        };
+        sink.worker.abort();
+        debug!(target: NET, url, "webhook removed");
+        true
+    }
+
+    /// Queue `event` for every webhook it matches.
+    pub fn send(&self, event: &Event) {
+        for sink in self.sinks.lock().unwrap().values() {
+            if !sink.webhook.matches(event) {
+                continue;
+            }
+            if sink.tx.try_send(event.clone()).is_err() {
+                warn!(target: NET, url = sink.webhook.url, "webhook queue full, event dropped");
+            }
+        }
+    }
+}
+
+async fn run<S>(
+    s: Arc<S>,
+    client: reqwest::Client,
+    webhook: Arc<Webhook>,
+    mut rx: mpsc::Receiver<Event>,
+) where
+    S: Satori + Send + Sync + 'static,
+{
+    loop {
+        let event = tokio::select! {
+            Some(event) = rx.recv() => event,
+            _ = s.stopped() => break,
+        };
+        let id = event.id;
+        let mut delay = RETRY_DELAY;
+        for attempt in 0..=webhook.retries {
+            if attempt > 0 {
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+            }
+            match deliver(&client, &webhook, &event).await {
+                Ok(()) => break,
+                Err(e) if attempt < webhook.retries => {
+                    let url = &webhook.url;
+                    debug!(target: NET, url, id, attempt, "webhook delivery failed: {e}")
+                }
+                Err(e) => {
+                    let url = &webhook.url;
+                    warn!(target: NET, url, id, "webhook delivery given up: {e}")
+                }
+            }
+        }
+    }
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    webhook: &Webhook,
+    event: &Event,
+) -> Result<(), reqwest::Error> {
+    // events always serialize
+    let body = serde_json::to_vec(event).unwrap();
+    let mut req = client
+        .post(&webhook.url)
+        .header("Content-Type", "application/json")
+        .header("X-Platform", &event.platform)
+        .header("X-Self-ID", &event.self_id);
+    if let Some(token) = &webhook.token {
+        req = req.bearer_auth(token);
+    }
+    if let Some(secret) = &webhook.secret {
+        req = req.header(HEADER_SIGNATURE, signature(secret, &body));
+    }
+    req.body(body).send().await?.error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use axum::{body::Bytes, extract::State, http::HeaderMap, routing::post};
+    use http::StatusCode;
+
+    use super::Webhooks;
+    use crate::{
+        dynamic::DynSatori,
+        impls::net::{signature, Webhook},
+        structs::Event,
+    };
+
+    type Seen = Arc<Mutex<Vec<(i64, bool)>>>;
+
+    #[tokio::test]
+    async fn test_delivery() {
+        // fails the first request, then records the rest
+        async fn receive(State(seen): State<Seen>, headers: HeaderMap, body: Bytes) -> StatusCode {
+            let event: Event = serde_json::from_slice(&body).unwrap();
+            let signed = headers.get("x-signature").map(|v| v.to_str().unwrap())
+                == Some(&signature("secret", &body));
+            let mut seen = seen.lock().unwrap();
+            seen.push((event.id, signed));
+            if seen.len() == 1 {
+                StatusCode::SERVICE_UNAVAILABLE
+            } else {
+                StatusCode::OK
+            }
+        }
+
+        let seen = Arc::new(Mutex::new(vec![]));
+        let receiver = axum::Router::new()
+            .route("/hook", post(receive))
+            .with_state(seen.clone());
+        let server = axum::Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0)))
+            .serve(receiver.into_make_service());
+        let url = format!("http://{}/hook", server.local_addr());
+        tokio::spawn(server);
+
+        let s = DynSatori::builder().build();
+        let webhooks = Webhooks::default();
+        webhooks.add(
+            &s,
+            Webhook {
+                secret: Some("secret".to_string()),
+                events: Some(vec!["message-created".to_string()]),
+                ..Webhook::new(url)
+            },
+        );
+        for (id, ty) in [
+            (1, "message-created"),
+            (2, "guild-added"),
+            (3, "message-created"),
+        ] {
+            webhooks.send(&Event {
+                id,
+                ty: ty.to_string(),
+                ..Default::default()
+            });
+        }
+        tokio::time::sleep(Duration::from_secs(1)).await;
+
+        assert_eq!(*seen.lock().unwrap(), vec![(1, true), (1, true), (3, true)]);
+    }
+}
@@ -17,8 +17,9 @@ use tracing::{debug, error, info, trace, warn};
 
 use crate::{
     api::{RawApiCall, TypedApiCall},
-    error::{MapSatoriError, SatoriError},
+    error::{ApiError, MapSatoriError, SatoriError},
     structs::{BotId, Channel, ChannelType, Event, Login, Message},
+    system::SystemEvent,
     Satori, SatoriSDK,
 };
 
@@ -58,14 +59,13 @@ impl Onebot11SDK {
         }
     }
 
-    async fn handle_ws_msg<S, T>(
+    async fn handle_ws_msg<T>(
         msg: Option<Result<WsMessage, WsError>>,
-        s: &Arc<S>,
+        events: &mpsc::UnboundedSender<Event>,
         ws_stream: &mut WebSocketStream<T>,
         action_resp_map: &mut HashMap<String, oneshot::Sender<ActionCallbackPayload>>,
     ) -> bool
     where
-        S: Satori + Send + Sync + 'static,
         T: AsyncRead + AsyncWrite + Unpin,
     {
         trace!(target: ONEBOT, "receive ws_msg: {:?}" ,msg);
@@ -75,7 +75,7 @@ impl Onebot11SDK {
                 match serde_json::from_str(&text) {
                     Ok(structs::EventOrActionResp::Event(ev)) => {
                         if let Some(ev) = Onebot11SDK::transform_event(ev) {
-                            s.handle_event(ev);
+                            let _ = events.send(ev);
                         }
                     }
                     Ok(structs::EventOrActionResp::ActionResp(resp)) => {
@@ -150,9 +150,22 @@ impl SatoriSDK for Onebot11SDK {
         }
         let (mut ws_stream, _) = connect_async(req).await.unwrap();
         info!(target: ONEBOT, "WebSocket connected with {addr}");
+        s.runtime()
+            .emit(SystemEvent::SdkConnected { sdk: addr.clone() });
 
         let mut action_rx = self.action_rx.lock().await;
         let mut action_resp_map = HashMap::<String, oneshot::Sender<ActionCallbackPayload>>::new();
+        // handlers may wait on action responses, so they must not hold up
+        // reading them
+        let (events, mut events_rx) = mpsc::unbounded_channel();
+        tokio::spawn({
+            let s = s.clone();
+            async move {
+                while let Some(ev) = events_rx.recv().await {
+                    s.handle_event(ev).await;
+                }
+            }
+        });
 
         loop {
             tokio::select! {
@@ -168,13 +181,14 @@ impl SatoriSDK for Onebot11SDK {
                         }
                     }
                 },
-                msg = ws_stream.next() => if !Onebot11SDK::handle_ws_msg(msg, s, &mut ws_stream, &mut action_resp_map).await { break },
+                msg = ws_stream.next() => if !Onebot11SDK::handle_ws_msg(msg, &events, &mut ws_stream, &mut action_resp_map).await { break },
                 _ = s.stopped() => {
                     let _ = ws_stream.send(WsMessage::Close(None)).await;
                     break;
                 }
             }
         }
+        s.runtime().emit(SystemEvent::SdkDisconnected { sdk: addr });
     }
 
     async fn call_api<S>(
@@ -207,6 +221,14 @@ impl SatoriSDK for Onebot11SDK {
                     _ => unreachable!(),
                 }
             }
+            TypedApiCall::MessageDelete { message_id, .. } => {
+                ("delete_msg", json!({ "message_id": message_id }))
+            }
+            TypedApiCall::ReactionCreate { .. }
+            | TypedApiCall::GuildMemberGet { .. }
+            | TypedApiCall::UserGet { .. }
+            | TypedApiCall::GuildGet { .. }
+            | TypedApiCall::ChannelGet { .. } => return Err(ApiError::NotFound.into()),
         };
         let echo = Alphanumeric.sample_string(&mut thread_rng(), 8);
         let action = structs::Action {
@@ -232,3 +254,103 @@ impl SatoriSDK for Onebot11SDK {
         vec![]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
+
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::json;
+    use tokio::net::TcpListener;
+
+    use super::{structs, Onebot11SDK, Onebot11SDKConfig, WsMessage};
+    use crate::{
+        dispatch::DispatchOptions, dynamic::DynSatori, error::SessionError, runtime::SatoriOptions,
+        session::Session, structs::Event, Satori, SatoriApp,
+    };
+
+    /// Answers every message.
+    struct Echo;
+
+    impl SatoriApp for Echo {
+        type Error = SessionError;
+
+        async fn start<S>(&self, _s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+        }
+
+        async fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> Result<(), SessionError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            if event.ty == "message-created" {
+                Session::new(s, event).send("pong").await?;
+            }
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handlers_calling_api() {
+        // a OneBot implementation sending two messages and answering actions
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut ws = tokio_tungstenite::accept_async(stream).await.unwrap();
+            for id in 1..=2 {
+                let message = json!({
+                    "post_type": "message",
+                    "message_type": "private",
+                    "message_id": id,
+                    "self_id": 1,
+                    "user_id": 2,
+                    "message": "ping",
+                });
+                ws.send(WsMessage::text(message.to_string())).await.unwrap();
+            }
+            let mut actions = vec![];
+            while actions.len() < 2 {
+                let Some(Ok(WsMessage::Text(text))) = ws.next().await else {
+                    panic!("connection closed");
+                };
+                let action: structs::Action = serde_json::from_str(&text).unwrap();
+                let resp = json!({
+                    "status": "ok",
+                    "retcode": 0,
+                    "data": {},
+                    "echo": action.echo,
+                });
+                ws.send(WsMessage::text(resp.to_string())).await.unwrap();
+                actions.push(action.action);
+            }
+            actions
+        });
+
+        let s = DynSatori::builder()
+            .sdk(Onebot11SDK::new(Onebot11SDKConfig {
+                host: "127.0.0.1".to_string(),
+                port,
+                access_token: None,
+                self_id: "1".to_string(),
+            }))
+            .app(Echo)
+            .options(SatoriOptions {
+                dispatch: DispatchOptions {
+                    concurrency: 1,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .build();
+        s.spawn().await;
+        let actions = tokio::time::timeout(Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(actions, ["send_private_msg", "send_private_msg"]);
+        s.shutdown().await;
+    }
+}
@@ -6,9 +6,28 @@ pub use core::*;
 mod macros;
 
 pub mod api;
+pub mod authority;
+pub mod cache;
+pub mod command;
+pub mod dedup;
+pub mod dispatch;
+pub mod dynamic;
 pub mod error;
+pub mod filter;
 pub mod impls;
+pub mod middleware;
+pub mod ratelimit;
+pub mod report;
+pub mod retry;
+pub mod router;
+pub mod routing;
+pub mod runtime;
+pub mod session;
 pub mod structs;
+pub mod system;
+#[cfg(test)]
+mod testing;
+pub mod upload;
 
 #[cfg(feature = "message")]
 pub mod message;
@@ -54,9 +54,10 @@ macro_rules! __satori_expand {
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __satori_impl_start_sdk {
-    ( ( $self:ident, $set:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {{
+    ( ( $self:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {{
         $(
-            $set.spawn({
+            let ( $($skip,)* ref s, .. ) = $self.sdk;
+            $self.runtime.spawn(std::any::type_name_of_val(s), {
                 let me = $self.clone();
                 async move {
                     let ( $($skip,)* ref s, .. ) = me.sdk;
@@ -70,9 +71,10 @@ macro_rules! __satori_impl_start_sdk {
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __satori_impl_start_app {
-    ( ( $self:ident, $set:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {{
+    ( ( $self:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {{
         $(
-            $set.spawn({
+            let ( $($skip,)* ref a, .. ) = $self.app;
+            $self.runtime.spawn(std::any::type_name_of_val(a), {
                 let me = $self.clone();
                 async move {
                     let ( $($skip,)* ref a, .. ) = me.app;
@@ -83,30 +85,83 @@ macro_rules! __satori_impl_start_app {
     }};
 }
 
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __satori_count {
+    () => { 0usize };
+    ($head:tt $($tail:tt)*) => { 1usize + $crate::__satori_count!($($tail)*) };
+}
+
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __satori_impl_call_api {
-    ( ( $self:ident, $bot:ident, $payload:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
-        tokio::select!(
-            $((s, true) = async { let ( $($skip,)* s, .. ) = &$self.sdk; (s, $crate::SatoriSDK::has_bot(s, $bot).await) } => $crate::SatoriSDK::call_api(s, $self, $bot, $payload).await,)*
-            else => Err($crate::error::SatoriError::InvalidBot),
-        )
+    ( ( $self:ident, $sdk:ident, $bot:ident, $payload:ident, $method:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
+        match $sdk {
+            $(
+                i if i == $crate::__satori_count!($($skip)*) => {
+                    let ( $($skip,)* s, .. ) = &$self.sdk;
+                    $crate::SatoriSDK::$method(s, $self, $bot, $payload).await
+                }
+            )*
+            _ => Err($crate::error::SatoriError::InvalidBot),
+        }
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __satori_impl_has_bot {
+    ( ( $self:ident, $bot:ident, $result:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
+        $(
+            let ( $($skip,)* s, .. ) = &$self.sdk;
+            if $crate::SatoriSDK::has_bot(s, $bot).await {
+                $result.push($crate::__satori_count!($($skip)*));
+            }
+        )*
     };
 }
 
 #[macro_export]
 #[doc(hidden)]
 macro_rules! __satori_impl_handle_event {
-    ( ( $self:ident, $event:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
+    ( ( $self:ident, $event:ident, $taken:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
         $(
-            tokio::spawn({
-                let me = $self.clone();
-                let event = $event.clone();
-                async move {
-                    let ( $($skip,)* a, .. ) = &me.app;
-                    $crate::SatoriApp::handle_event(a, &me, event).await
-                }
-            });
+            let index = $crate::__satori_count!($($skip)*);
+            if !$taken.is_some_and(|t| t.skips(index)) {
+                $self.runtime.dispatcher(index).dispatch(&$event, {
+                    let ( $($skip,)* a, .. ) = &$self.app;
+                    let name = std::any::type_name_of_val(a);
+                    let me = $self.clone();
+                    let event = $event.clone();
+                    $self.runtime.track($crate::report::guard($self.clone(), name, &$event, async move {
+                        let ( $($skip,)* a, .. ) = &me.app;
+                        let handled = $crate::SatoriApp::handle_event(a, &me, event);
+                        $crate::session::in_app(index, handled).await.map_err(Into::into)
+                    }))
+                }).await;
+            }
+        )*
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __satori_impl_app_names {
+    ( ( $self:ident, $result:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
+        $(
+            let ( $($skip,)* a, .. ) = &$self.app;
+            $result.push(std::any::type_name_of_val(a));
+        )*
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __satori_impl_proxy_urls {
+    ( ( $self:ident, $result:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
+        $(
+            let ( $($skip,)* s, .. ) = &$self.sdk;
+            $result.append(&mut $crate::SatoriSDK::proxy_urls(s));
         )*
     };
 }
@@ -117,7 +172,9 @@ macro_rules! __satori_impl_get_logins {
     ( ( $self:ident, $result:ident ), $( ( $($skip:tt)* ) $e:tt, )* ) => {
         $(
             let ( $($skip,)* s, .. ) = &$self.sdk;
-            $result.append(&mut $crate::SatoriSDK::get_logins(s).await);
+            let mut logins = $crate::SatoriSDK::get_logins(s).await;
+            $self.runtime.routes().claim_logins($crate::__satori_count!($($skip)*), &logins);
+            $result.append(&mut logins);
         )*
     };
 }
@@ -131,40 +188,46 @@ macro_rules! satori {
         $vis struct $name {
             sdk: $crate::__satori_wrap_tuple!($s),
             app: $crate::__satori_wrap_tuple!($a),
-            stop: tokio_util::sync::CancellationToken,
-            set: tokio::sync::RwLock<tokio::task::JoinSet<()>>,
+            runtime: $crate::runtime::Runtime,
         }
 
         impl $name {
             $vis fn new(sdk: $s, app: $a) -> std::sync::Arc<Self> {
+                Self::with_options(sdk, app, Default::default())
+            }
+
+            $vis fn with_options(sdk: $s, app: $a, options: $crate::runtime::SatoriOptions) -> std::sync::Arc<Self> {
                 std::sync::Arc::new(Self {
                     sdk: $crate::__satori_convert_tuple!($s, sdk),
                     app: $crate::__satori_convert_tuple!($a, app),
-                    stop: tokio_util::sync::CancellationToken::new(),
-                    set: tokio::sync::RwLock::new(tokio::task::JoinSet::new()),
+                    runtime: $crate::runtime::Runtime::new(options),
                 })
             }
         }
 
         impl $crate::Satori for $name {
             async fn spawn(self: &std::sync::Arc<Self>) {
-                tracing::info!(target: $crate::SATORI, "Starting...");
-                let mut set = self.set.write().await;
-                $crate::__satori_expand!(__satori_impl_start_sdk, (self, set), $s);
-                $crate::__satori_expand!(__satori_impl_start_app, (self, set), $a);
+                if !self.runtime.begin_start() {
+                    return;
+                }
+                $crate::__satori_expand!(__satori_impl_start_sdk, (self), $s);
+                $crate::__satori_expand!(__satori_impl_start_app, (self), $a);
+                self.runtime.finish_start();
+                let mut apps = vec![];
+                $crate::__satori_expand!(__satori_impl_app_names, (self, apps), $a);
+                $crate::system::started(self, apps);
             }
 
             async fn start(self: &std::sync::Arc<Self>) {
                 $crate::Satori::spawn(self).await;
-                while !self.set.read().await.is_empty() {
-                    let _ = self.set.write().await.join_next().await; 
-                }
+                self.runtime.join().await;
+                $crate::Satori::shutdown(self).await;
             }
 
             async fn shutdown(self: &std::sync::Arc<Self>) {
-                tracing::info!(target: $crate::SATORI, "Stopping...");
-                self.stop.cancel();
-                self.set.write().await.shutdown().await;
+                let mut apps = vec![];
+                $crate::__satori_expand!(__satori_impl_app_names, (self, apps), $a);
+                self.runtime.shutdown($crate::system::stopping(self, apps)).await;
             }
 
             async fn call_api<T>(self: &std::sync::Arc<Self>, bot: &$crate::structs::BotId, payload: T) -> Result<serde_json::Value, $crate::error::SatoriError>
@@ -173,12 +236,121 @@ macro_rules! satori {
             {
                 let payload = payload.into_raw();
                 tracing::debug!(target: $crate::SATORI, ?bot, ?payload, "call api");
-                $crate::__satori_expand!(__satori_impl_call_api, (self, bot, payload), $s)
+                let me = self.clone();
+                let endpoint = move |bot: $crate::structs::BotId, payload: $crate::api::RawApiCall| -> $crate::dynamic::BoxFuture<'static, _> {
+                    let me = me.clone();
+                    Box::pin(async move {
+                        let (me, bot) = (&me, &bot);
+                        me.runtime.routes().call(
+                            bot,
+                            payload,
+                            || async move {
+                                let mut claims = vec![];
+                                $crate::__satori_expand!(__satori_impl_has_bot, (me, bot, claims), $s);
+                                claims
+                            },
+                            |sdk, payload| async move {
+                                $crate::__satori_expand!(__satori_impl_call_api, (me, sdk, bot, payload, call_api), $s)
+                            },
+                        ).await
+                    })
+                };
+                self.runtime.track(self.runtime.middlewares().call_api(bot, payload, &endpoint)).await
+            }
+
+            async fn call_internal(self: &std::sync::Arc<Self>, bot: &$crate::structs::BotId, payload: $crate::api::RawApiCall) -> Result<serde_json::Value, $crate::error::SatoriError> {
+                tracing::debug!(target: $crate::SATORI, ?bot, ?payload, "call internal api");
+                let payload = $crate::api::RawApiCall {
+                    method: format!("{}{}", $crate::api::INTERNAL_PREFIX, payload.method),
+                    body: payload.body,
+                };
+                let me = self.clone();
+                let endpoint = move |bot: $crate::structs::BotId, mut payload: $crate::api::RawApiCall| -> $crate::dynamic::BoxFuture<'static, _> {
+                    let me = me.clone();
+                    Box::pin(async move {
+                        if let Some(method) = payload.method.strip_prefix($crate::api::INTERNAL_PREFIX) {
+                            payload.method = method.to_string();
+                        }
+                        let (me, bot) = (&me, &bot);
+                        me.runtime.routes().call(
+                            bot,
+                            payload,
+                            || async move {
+                                let mut claims = vec![];
+                                $crate::__satori_expand!(__satori_impl_has_bot, (me, bot, claims), $s);
+                                claims
+                            },
+                            |sdk, payload| async move {
+                                $crate::__satori_expand!(__satori_impl_call_api, (me, sdk, bot, payload, call_internal), $s)
+                            },
+                        ).await
+                    })
+                };
+                self.runtime.track(self.runtime.middlewares().call_api(bot, payload, &endpoint)).await
+            }
+
+            async fn upload(self: &std::sync::Arc<Self>, bot: &$crate::structs::BotId, file: $crate::upload::Upload) -> Result<String, $crate::error::SatoriError> {
+                tracing::debug!(target: $crate::SATORI, ?bot, filename = file.filename, "upload");
+                let payload = $crate::api::RawApiCall {
+                    method: "upload.create".to_string(),
+                    body: serde_json::json!({ "filename": file.filename, "mime": file.mime }),
+                };
+                let me = self.clone();
+                let endpoint = move |bot: $crate::structs::BotId, payload: $crate::api::RawApiCall| -> $crate::dynamic::BoxFuture<'static, _> {
+                    let (me, file) = (me.clone(), file.clone());
+                    Box::pin(async move {
+                        let (me, bot) = (&me, &bot);
+                        let result = me.runtime.routes().call(
+                            bot,
+                            payload,
+                            || async move {
+                                let mut claims = vec![];
+                                $crate::__satori_expand!(__satori_impl_has_bot, (me, bot, claims), $s);
+                                claims
+                            },
+                            |sdk, _| {
+                                let file = file.clone();
+                                async move {
+                                    $crate::__satori_expand!(__satori_impl_call_api, (me, sdk, bot, file, upload), $s)
+                                }
+                            },
+                        ).await;
+                        let url = match result {
+                            Err($crate::error::SatoriError::ApiError($crate::error::ApiError::NotFound)) => me.runtime.uploads().put(bot, file).await?,
+                            result => result?,
+                        };
+                        Ok(serde_json::Value::String(url))
+                    })
+                };
+                $crate::upload::into_url(self.runtime.track(self.runtime.middlewares().call_api(bot, payload, &endpoint)).await?)
             }
 
-            fn handle_event(self: &std::sync::Arc<Self>, event: $crate::structs::Event) {
+            async fn handle_event(self: &std::sync::Arc<Self>, event: $crate::structs::Event) {
                 tracing::debug!(target: $crate::SATORI, ?event, "handle event");
-                $crate::__satori_expand!(__satori_impl_handle_event, (self, event), $a);
+                if !self.runtime.accepts(&event) {
+                    tracing::debug!(target: $crate::SATORI, id = event.id, state = ?self.runtime.state(), "event dropped");
+                    return;
+                }
+                if self.runtime.dedup().is_duplicate(&event) {
+                    tracing::debug!(target: $crate::SATORI, id = event.id, "duplicate event dropped");
+                    return;
+                }
+                if let Some(bot) = $crate::routing::login_bot(&event) {
+                    let (me, bot) = (self, &bot);
+                    let mut claims = vec![];
+                    $crate::__satori_expand!(__satori_impl_has_bot, (me, bot, claims), $s);
+                    self.runtime.routes().set_claims(bot, claims);
+                }
+                let me = self.clone();
+                let endpoint = move |event: $crate::structs::Event| -> $crate::dynamic::BoxFuture<'static, ()> {
+                    let me = me.clone();
+                    Box::pin(async move {
+                        let me = &me;
+                        let taken = me.runtime.waiters().offer(&event);
+                        $crate::__satori_expand!(__satori_impl_handle_event, (me, event, taken), $a);
+                    })
+                };
+                self.runtime.middlewares().handle_event(event, &endpoint).await
             }
 
             async fn get_logins(self: &std::sync::Arc<Self>) -> Vec<$crate::structs::Login> {
@@ -187,8 +359,22 @@ macro_rules! satori {
                 result
             }
 
+            fn proxy_urls(self: &std::sync::Arc<Self>) -> Vec<String> {
+                let mut result = vec![];
+                $crate::__satori_expand!(__satori_impl_proxy_urls, (self, result), $s);
+                result
+            }
+
+            fn routes(self: &std::sync::Arc<Self>) -> Vec<$crate::routing::Route> {
+                self.runtime.routes().routes()
+            }
+
+            fn runtime(self: &std::sync::Arc<Self>) -> &$crate::runtime::Runtime {
+                &self.runtime
+            }
+
             async fn stopped(self: &std::sync::Arc<Self>) {
-                self.stop.cancelled().await
+                self.runtime.stopped().await
             }
         }
 
@@ -197,9 +383,10 @@ macro_rules! satori {
             $vis async fn start_with_graceful_shutdown(self: &std::sync::Arc<Self>, signal: impl std::future::Future) {
                 $crate::Satori::spawn(self).await;
                 tokio::select! {
-                    _ = signal => $crate::Satori::shutdown(self).await,
-                    _ = async { while !self.set.read().await.is_empty() {} } => {}
+                    _ = signal => {}
+                    _ = self.runtime.join() => {}
                 }
+                $crate::Satori::shutdown(self).await
             }
         }
     };
@@ -0,0 +1,341 @@
+use std::future::Future;
+
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::{
+    api::RawApiCall,
+    dynamic::BoxFuture,
+    error::SatoriError,
+    structs::{BotId, Event},
+    SATORI,
+};
+
+pub trait EventMiddleware {
+    /// Inspect, transform or drop an event before it reaches the apps.
+    ///
+    /// Call `next.run(event)` to pass the event on; not calling it drops it.
+    fn handle_event(&self, event: Event, next: EventNext<'_>) -> impl Future<Output = ()> + Send;
+}
+
+pub trait ApiMiddleware {
+    /// Wrap an API call on its way to the SDK.
+    ///
+    /// `next` is `Copy`, so it can be run several times (e.g. for retries) or
+    /// not at all to short-circuit the call.
+    fn call_api(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        next: ApiNext<'_>,
+    ) -> impl Future<Output = Result<Value, SatoriError>> + Send;
+}
+
+trait DynEventMiddleware: Send + Sync {
+    fn handle_event<'a>(&'a self, event: Event, next: EventNext<'a>) -> BoxFuture<'a, ()>;
+}
+
+impl<T> DynEventMiddleware for T
+where
+    T: EventMiddleware + Send + Sync,
+{
+    fn handle_event<'a>(&'a self, event: Event, next: EventNext<'a>) -> BoxFuture<'a, ()> {
+        Box::pin(EventMiddleware::handle_event(self, event, next))
+    }
+}
+
+trait DynApiMiddleware: Send + Sync {
+    fn call_api<'a>(
+        &'a self,
+        bot: &'a BotId,
+        payload: RawApiCall,
+        next: ApiNext<'a>,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>>;
+}
+
+impl<T> DynApiMiddleware for T
+where
+    T: ApiMiddleware + Send + Sync,
+{
+    fn call_api<'a>(
+        &'a self,
+        bot: &'a BotId,
+        payload: RawApiCall,
+        next: ApiNext<'a>,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>> {
+        Box::pin(ApiMiddleware::call_api(self, bot, payload, next))
+    }
+}
+
+pub type EventEndpoint = dyn Fn(Event) -> BoxFuture<'static, ()> + Send + Sync;
+
+pub type ApiEndpoint =
+    dyn Fn(BotId, RawApiCall) -> BoxFuture<'static, Result<Value, SatoriError>> + Send + Sync;
+
+#[derive(Clone, Copy)]
+pub struct EventNext<'a> {
+    chain: &'a [Box<dyn DynEventMiddleware>],
+    endpoint: &'a EventEndpoint,
+}
+
+impl<'a> EventNext<'a> {
+    pub fn run(self, event: Event) -> BoxFuture<'a, ()> {
+        match self.chain.split_first() {
+            Some((m, chain)) => m.handle_event(
+                event,
+                EventNext {
+                    chain,
+                    endpoint: self.endpoint,
+                },
+            ),
+            None => (self.endpoint)(event),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+pub struct ApiNext<'a> {
+    chain: &'a [Box<dyn DynApiMiddleware>],
+    endpoint: &'a ApiEndpoint,
+}
+
+impl<'a> ApiNext<'a> {
+    pub fn run(
+        self,
+        bot: &BotId,
+        payload: RawApiCall,
+    ) -> BoxFuture<'a, Result<Value, SatoriError>> {
+        match self.chain.split_first() {
+            Some((m, chain)) => {
+                let bot = bot.clone();
+                let next = ApiNext {
+                    chain,
+                    endpoint: self.endpoint,
+                };
+                Box::pin(async move { m.call_api(&bot, payload, next).await })
+            }
+            None => (self.endpoint)(bot.clone(), payload),
+        }
+    }
+}
+
+/// Event and API middlewares, run in the order they were added.
+#[derive(Default)]
+pub struct Middlewares {
+    event: Vec<Box<dyn DynEventMiddleware>>,
+    api: Vec<Box<dyn DynApiMiddleware>>,
+}
+
+impl Middlewares {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn event<M>(mut self, middleware: M) -> Self
+    where
+        M: EventMiddleware + Send + Sync + 'static,
+    {
+        self.event.push(Box::new(middleware));
+        self
+    }
+
+    pub fn api<M>(mut self, middleware: M) -> Self
+    where
+        M: ApiMiddleware + Send + Sync + 'static,
+    {
+        self.api.push(Box::new(middleware));
+        self
+    }
+
+    pub async fn handle_event(&self, event: Event, endpoint: &EventEndpoint) {
+        EventNext {
+            chain: &self.event,
+            endpoint,
+        }
+        .run(event)
+        .await
+    }
+
+    pub async fn call_api(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        endpoint: &ApiEndpoint,
+    ) -> Result<Value, SatoriError> {
+        ApiNext {
+            chain: &self.api,
+            endpoint,
+        }
+        .run(bot, payload)
+        .await
+    }
+}
+
+impl std::fmt::Debug for Middlewares {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Middlewares")
+            .field("event", &self.event.len())
+            .field("api", &self.api.len())
+            .finish()
+    }
+}
+
+/// Drops events sent by the bot itself.
+#[derive(Debug, Default)]
+pub struct IgnoreSelf;
+
+impl EventMiddleware for IgnoreSelf {
+    async fn handle_event(&self, event: Event, next: EventNext<'_>) {
+        if let Some(user) = &event.user {
+            if user.id == event.self_id {
+                debug!(target: SATORI, id = event.id, "self event ignored");
+                return;
+            }
+        }
+        next.run(event).await
+    }
+}
+
+/// Logs every event and API call.
+#[derive(Debug, Default)]
+pub struct Logger;
+
+impl EventMiddleware for Logger {
+    async fn handle_event(&self, event: Event, next: EventNext<'_>) {
+        info!(
+            target: SATORI,
+            id = event.id,
+            ty = event.ty,
+            platform = event.platform,
+            self_id = event.self_id,
+            "event"
+        );
+        next.run(event).await
+    }
+}
+
+impl ApiMiddleware for Logger {
+    async fn call_api(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        next: ApiNext<'_>,
+    ) -> Result<Value, SatoriError> {
+        let method = payload.method.clone();
+        let result = next.run(bot, payload).await;
+        match &result {
+            Ok(_) => info!(target: SATORI, ?bot, method, "api call succeeded"),
+            Err(e) => info!(target: SATORI, ?bot, method, "api call failed: {e}"),
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::{json, Value};
+
+    use super::{ApiMiddleware, ApiNext, EventMiddleware, EventNext, IgnoreSelf, Middlewares};
+    use crate::{
+        api::RawApiCall,
+        error::SatoriError,
+        structs::{BotId, Event, User},
+    };
+
+    struct Rename(&'static str);
+
+    impl ApiMiddleware for Rename {
+        async fn call_api(
+            &self,
+            bot: &BotId,
+            mut payload: RawApiCall,
+            next: ApiNext<'_>,
+        ) -> Result<Value, SatoriError> {
+            payload.method = format!("{}.{}", self.0, payload.method);
+            next.run(bot, payload).await
+        }
+    }
+
+    struct Cached;
+
+    impl ApiMiddleware for Cached {
+        async fn call_api(
+            &self,
+            bot: &BotId,
+            payload: RawApiCall,
+            next: ApiNext<'_>,
+        ) -> Result<Value, SatoriError> {
+            if payload.method == "cached" {
+                return Ok(json!("hit"));
+            }
+            next.run(bot, payload).await
+        }
+    }
+
+    struct Tag;
+
+    impl EventMiddleware for Tag {
+        async fn handle_event(&self, mut event: Event, next: EventNext<'_>) {
+            event.ty = format!("tagged-{}", event.ty);
+            next.run(event).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_api_chain() {
+        let m = Middlewares::new()
+            .api(Cached)
+            .api(Rename("a"))
+            .api(Rename("b"));
+        let bot = BotId {
+            id: "1".to_string(),
+            platform: "test".to_string(),
+        };
+        let call = |method: &str| RawApiCall {
+            method: method.to_string(),
+            body: json!(null),
+        };
+        let endpoint =
+            |_bot: BotId, payload: RawApiCall| -> crate::dynamic::BoxFuture<'static, _> {
+                Box::pin(async move { Ok(json!(payload.method)) })
+            };
+
+        assert_eq!(
+            m.call_api(&bot, call("x"), &endpoint).await.unwrap(),
+            json!("b.a.x")
+        );
+        assert_eq!(
+            m.call_api(&bot, call("cached"), &endpoint).await.unwrap(),
+            json!("hit")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_chain() {
+        let m = Middlewares::new().event(IgnoreSelf).event(Tag);
+        let seen = Arc::new(Mutex::new(vec![]));
+        let endpoint = {
+            let seen = seen.clone();
+            move |event: Event| -> crate::dynamic::BoxFuture<'static, ()> {
+                seen.lock().unwrap().push(event.ty);
+                Box::pin(async {})
+            }
+        };
+
+        let event = |user: &str| Event {
+            ty: "message-created".to_string(),
+            self_id: "1".to_string(),
+            user: Some(User {
+                id: user.to_string(),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        m.handle_event(event("1"), &endpoint).await;
+        m.handle_event(event("2"), &endpoint).await;
+
+        assert_eq!(*seen.lock().unwrap(), vec!["tagged-message-created"]);
+    }
+}
@@ -0,0 +1,369 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::Instant;
+use tracing::debug;
+
+use crate::{
+    api::RawApiCall,
+    authority::method_matches,
+    error::{ApiError, ConfigError, SatoriError},
+    middleware::{ApiMiddleware, ApiNext},
+    structs::BotId,
+    SATORI,
+};
+
+/// A token bucket applied to calls of matching methods.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateLimitRule {
+    /// Method pattern, see [`method_matches`].
+    pub methods: String,
+    /// Calls allowed per second on average.
+    pub rate: f64,
+    /// Calls allowed at once after a quiet period.
+    pub burst: u32,
+    /// Keep a bucket per channel (`channel_id` in the call body).
+    pub per_channel: bool,
+    /// Keep a bucket per method instead of sharing one between all matching.
+    pub per_method: bool,
+}
+
+impl Default for RateLimitRule {
+    fn default() -> Self {
+        Self {
+            methods: "*".to_string(),
+            rate: 1.0,
+            burst: 5,
+            per_channel: false,
+            per_method: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Every matching rule applies; buckets are always kept per bot.
+    pub rules: Vec<RateLimitRule>,
+    /// Whether excess calls wait for their turn instead of failing with
+    /// [`ApiError::RateLimited`].
+    pub queue: bool,
+    /// Calls allowed to wait per bucket; further ones fail.
+    pub max_queue: usize,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            rules: vec![],
+            queue: true,
+            max_queue: 64,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    rule: usize,
+    bot: BotId,
+    channel: Option<String>,
+    method: Option<String>,
+}
+
+/// How often buckets back at their burst are dropped.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+struct Bucket {
+    /// Negative when calls are waiting for tokens they already took.
+    tokens: f64,
+    updated: Instant,
+    queued: usize,
+}
+
+impl Bucket {
+    fn tokens_at(&self, rule: &RateLimitRule, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * rule.rate).min(rule.burst as f64)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    buckets: HashMap<Key, Bucket>,
+    /// Bots the platform asked to back off, until when.
+    paused: HashMap<BotId, Instant>,
+    pruned: Option<Instant>,
+}
+
+/// Throttles outbound API calls with token buckets.
+///
+/// Install it with [`Middlewares::api`](crate::middleware::Middlewares::api).
+/// Upstream [`ApiError::RateLimited`] errors with a delay pause all calls of
+/// their bot for that long.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    config: Arc<RateLimitConfig>,
+    state: Arc<Mutex<State>>,
+}
+
+/// Releases a queued call's place when dropped.
+struct Queued<'a> {
+    limiter: &'a RateLimiter,
+    keys: Vec<Key>,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        let mut state = self.limiter.state.lock().unwrap();
+        for key in &self.keys {
+            if let Some(bucket) = state.buckets.get_mut(key) {
+                bucket.queued -= 1;
+            }
+        }
+    }
+}
+
+impl RateLimiter {
+    /// Fails if a rule's rate isn't a positive number.
+    pub fn new(config: RateLimitConfig) -> Result<Self, ConfigError> {
+        let invalid = config
+            .rules
+            .iter()
+            .find(|rule| !(rule.rate.is_finite() && rule.rate > 0.0));
+        if let Some(rule) = invalid {
+            return Err(ConfigError::InvalidRate {
+                methods: rule.methods.clone(),
+                rate: rule.rate,
+            });
+        }
+        Ok(Self {
+            config: Arc::new(config),
+            ..Default::default()
+        })
+    }
+
+    /// Calls waiting for their turn.
+    pub fn queued(&self) -> usize {
+        let state = self.state.lock().unwrap();
+        state.buckets.values().map(|b| b.queued).sum()
+    }
+
+    /// Calls of `bot` waiting for their turn.
+    pub fn queued_for(&self, bot: &BotId) -> usize {
+        let state = self.state.lock().unwrap();
+        state
+            .buckets
+            .iter()
+            .filter(|(key, _)| key.bot == *bot)
+            .map(|(_, b)| b.queued)
+            .sum()
+    }
+
+    fn keys(&self, bot: &BotId, payload: &RawApiCall) -> Vec<Key> {
+        let channel = payload.body.get("channel_id").and_then(Value::as_str);
+        self.config
+            .rules
+            .iter()
+            .enumerate()
+            .filter(|(_, rule)| method_matches(&rule.methods, &payload.method))
+            .map(|(i, rule)| Key {
+                rule: i,
+                bot: bot.clone(),
+                channel: channel.filter(|_| rule.per_channel).map(Into::into),
+                method: rule.per_method.then(|| payload.method.clone()),
+            })
+            .collect()
+    }
+
+    /// Take a token from every bucket of the call, returning how long to
+    /// wait before sending it, or fail if it may not wait.
+    fn acquire(&self, bot: &BotId, keys: &[Key]) -> Result<Duration, SatoriError> {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+        if state
+            .pruned
+            .is_none_or(|at| now.duration_since(at) >= PRUNE_INTERVAL)
+        {
+            // a full bucket is the same as a new one
+            let rules = &self.config.rules;
+            state.buckets.retain(|key, bucket| {
+                bucket.queued > 0
+                    || bucket.tokens_at(&rules[key.rule], now) < rules[key.rule].burst as f64
+            });
+            state.pruned = Some(now);
+        }
+        let pause = state
+            .paused
+            .get(bot)
+            .map(|until| until.saturating_duration_since(now))
+            .unwrap_or_default();
+        let mut wait = pause;
+        for key in keys {
+            let rule = &self.config.rules[key.rule];
+            let bucket = state.buckets.entry(key.clone()).or_insert(Bucket {
+                tokens: rule.burst as f64,
+                updated: now,
+                queued: 0,
+            });
+            bucket.tokens = bucket.tokens_at(rule, now);
+            bucket.updated = now;
+            if bucket.tokens < 1.0 {
+                let needed = Duration::from_secs_f64((1.0 - bucket.tokens) / rule.rate);
+                if !self.config.queue || bucket.queued >= self.config.max_queue {
+                    return Err(ApiError::RateLimited(Some(needed.max(pause))).into());
+                }
+                wait = wait.max(needed);
+            }
+        }
+        if !wait.is_zero() && !self.config.queue {
+            return Err(ApiError::RateLimited(Some(wait)).into());
+        }
+        for key in keys {
+            let bucket = state.buckets.get_mut(key).unwrap();
+            bucket.tokens -= 1.0;
+            bucket.queued += 1;
+        }
+        Ok(wait)
+    }
+
+    /// Stop sending calls of `bot` for `delay`.
+    fn pause(&self, bot: &BotId, delay: Duration) {
+        let until = Instant::now() + delay;
+        let mut state = self.state.lock().unwrap();
+        state.paused.retain(|_, until| *until > Instant::now());
+        let paused = state.paused.entry(bot.clone()).or_insert(until);
+        *paused = (*paused).max(until);
+    }
+}
+
+impl ApiMiddleware for RateLimiter {
+    async fn call_api(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        next: ApiNext<'_>,
+    ) -> Result<Value, SatoriError> {
+        let keys = self.keys(bot, &payload);
+        let wait = self.acquire(bot, &keys)?;
+        let queued = Queued {
+            limiter: self,
+            keys,
+        };
+        if !wait.is_zero() {
+            debug!(target: SATORI, ?bot, method = payload.method, ?wait, "api call queued");
+            tokio::time::sleep(wait).await;
+        }
+        drop(queued);
+        let result = next.run(bot, payload).await;
+        if let Err(SatoriError::ApiError(ApiError::RateLimited(Some(delay)))) = &result {
+            debug!(target: SATORI, ?bot, ?delay, "rate limited by platform");
+            self.pause(bot, *delay);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+    use tokio::time::Instant;
+
+    use super::{RateLimitConfig, RateLimitRule, RateLimiter};
+    use crate::{
+        api::RawApiCall,
+        dynamic::BoxFuture,
+        error::{ApiError, ConfigError, SatoriError},
+        middleware::Middlewares,
+        structs::BotId,
+        testing::bot,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_rate_limit() {
+        let rule = RateLimitRule {
+            methods: "message.*".to_string(),
+            rate: 10.0,
+            burst: 2,
+            per_channel: true,
+            ..Default::default()
+        };
+        let limiter = RateLimiter::new(RateLimitConfig {
+            rules: vec![rule.clone()],
+            max_queue: 1,
+            ..Default::default()
+        })
+        .unwrap();
+        for rate in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let config = RateLimitConfig {
+                rules: vec![RateLimitRule {
+                    rate,
+                    ..rule.clone()
+                }],
+                ..Default::default()
+            };
+            assert!(matches!(
+                RateLimiter::new(config),
+                Err(ConfigError::InvalidRate { .. })
+            ));
+        }
+        let m = Middlewares::new().api(limiter.clone());
+        let endpoint = |_: BotId, _: RawApiCall| -> BoxFuture<'static, _> {
+            Box::pin(async { Ok(json!(null)) })
+        };
+        let bot = bot();
+        let send = |channel: &str| RawApiCall {
+            method: "message.create".to_string(),
+            body: json!({ "channel_id": channel }),
+        };
+
+        let start = Instant::now();
+        m.call_api(&bot, send("a"), &endpoint).await.unwrap();
+        m.call_api(&bot, send("a"), &endpoint).await.unwrap();
+        m.call_api(&bot, send("b"), &endpoint).await.unwrap();
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        // the third call to a channel waits, and a fourth can't queue behind it
+        let waiting = m.call_api(&bot, send("a"), &endpoint);
+        let rejected = async {
+            tokio::task::yield_now().await;
+            assert_eq!(limiter.queued_for(&bot), 1);
+            m.call_api(&bot, send("a"), &endpoint).await
+        };
+        let (waited, rejected) = tokio::join!(waiting, rejected);
+        waited.unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(100));
+        assert!(matches!(
+            rejected,
+            Err(SatoriError::ApiError(ApiError::RateLimited(Some(_))))
+        ));
+        assert_eq!(limiter.queued(), 0);
+
+        // an upstream retry-after pauses the bot
+        let limited = |_: BotId, _: RawApiCall| -> BoxFuture<'static, _> {
+            Box::pin(async { Err(ApiError::RateLimited(Some(Duration::from_secs(2))).into()) })
+        };
+        let other = RawApiCall {
+            method: "guild.get".to_string(),
+            body: json!({}),
+        };
+        assert!(m.call_api(&bot, other.clone(), &limited).await.is_err());
+        let start = Instant::now();
+        m.call_api(&bot, other, &endpoint).await.unwrap();
+        assert!(start.elapsed() >= Duration::from_secs(2));
+
+        // idle channels are forgotten
+        assert_eq!(limiter.state.lock().unwrap().buckets.len(), 2);
+        tokio::time::advance(Duration::from_secs(60)).await;
+        m.call_api(&bot, send("c"), &endpoint).await.unwrap();
+        assert_eq!(limiter.state.lock().unwrap().buckets.len(), 1);
+    }
+}
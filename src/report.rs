@@ -0,0 +1,148 @@
+use std::{any::Any, future::Future, sync::Arc};
+
+use tracing::error;
+
+use crate::{
+    api::SatoriApi,
+    error::AppError,
+    structs::{BotId, Event},
+    system::{SystemEvent, APP_ERROR, INTERNAL},
+    Satori, SATORI,
+};
+
+/// Where to report errors returned (or panics raised) by
+/// [`SatoriApp::handle_event`](crate::SatoriApp::handle_event).
+#[derive(Clone)]
+pub enum ErrorHook {
+    /// Log the error.
+    Log,
+    /// Emit a [`SystemEvent::AppError`] to all apps.
+    Event,
+    /// Send a message to an admin channel.
+    Notice {
+        bot: BotId,
+        channel_id: String,
+    },
+    Custom(Arc<dyn Fn(&ErrorReport) + Send + Sync>),
+}
+
+impl std::fmt::Debug for ErrorHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Log => write!(f, "Log"),
+            Self::Event => write!(f, "Event"),
+            Self::Notice { bot, channel_id } => f
+                .debug_struct("Notice")
+                .field("bot", bot)
+                .field("channel_id", channel_id)
+                .finish(),
+            Self::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ErrorReport {
+    pub app: &'static str,
+    pub event_id: i64,
+    pub event_type: String,
+    pub bot: BotId,
+    pub channel_id: Option<String>,
+    pub error: AppError,
+}
+
+impl std::fmt::Display for ErrorReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} failed on event {} ({}): {}",
+            self.app, self.event_id, self.event_type, self.error
+        )
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(s) => *s,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(s) => s.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
+
+/// Run an event handler, reporting its error or panic to the error hooks.
+pub fn guard<S, F>(
+    s: Arc<S>,
+    app: &'static str,
+    event: &Event,
+    task: F,
+) -> impl Future<Output = ()> + Send + 'static
+where
+    S: Satori + Send + Sync + 'static,
+    F: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    let event_id = event.id;
+    let event_type = match &event.internal_type {
+        Some(ty) if event.ty == INTERNAL => ty.clone(),
+        _ => event.ty.clone(),
+    };
+    let bot = BotId {
+        id: event.self_id.clone(),
+        platform: event.platform.clone(),
+    };
+    let channel_id = event.channel.as_ref().map(|c| c.id.clone());
+    async move {
+        let error = match tokio::spawn(task).await {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => AppError::Error(e),
+            Err(e) if e.is_panic() => AppError::Panic(panic_message(e.into_panic())),
+            Err(_) => return,
+        };
+        report(
+            &s,
+            ErrorReport {
+                app,
+                event_id,
+                event_type,
+                bot,
+                channel_id,
+                error,
+            },
+        )
+    }
+}
+
+pub fn report<S>(s: &Arc<S>, report: ErrorReport)
+where
+    S: Satori + Send + Sync + 'static,
+{
+    for hook in s.runtime().error_hooks() {
+        match hook {
+            ErrorHook::Log => error!(target: SATORI, "{report}"),
+            // never report failures of the report itself, to avoid loops
+            ErrorHook::Event if report.event_type != APP_ERROR => {
+                s.runtime().emit(SystemEvent::AppError {
+                    app: report.app.to_string(),
+                    event_id: report.event_id,
+                    event_type: report.event_type.clone(),
+                    platform: report.bot.platform.clone(),
+                    self_id: report.bot.id.clone(),
+                    channel_id: report.channel_id.clone(),
+                    error: report.error.to_string(),
+                })
+            }
+            ErrorHook::Event => {}
+            ErrorHook::Notice { bot, channel_id } => {
+                let (s, bot, channel_id) = (s.clone(), bot.clone(), channel_id.clone());
+                let content = report.to_string();
+                tokio::spawn(async move {
+                    if let Err(e) = s.create_message(&bot, channel_id, content).await {
+                        error!(target: SATORI, "failed to send error notice: {e}");
+                    }
+                });
+            }
+            ErrorHook::Custom(f) => f(&report),
+        }
+    }
+}
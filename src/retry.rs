@@ -0,0 +1,183 @@
+use std::{sync::Arc, time::Duration};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::{
+    api::RawApiCall,
+    authority::method_matches,
+    error::{ApiError, SatoriError},
+    middleware::{ApiMiddleware, ApiNext},
+    structs::BotId,
+    SATORI,
+};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Attempts after the first one.
+    pub retries: u32,
+    /// Delay before the first retry in milliseconds, doubled for each
+    /// further one.
+    pub delay_ms: u64,
+    /// Longest delay between attempts in milliseconds.
+    pub max_delay_ms: u64,
+    /// Patterns of further methods safe to retry; `*.get` and `*.list`
+    /// methods always are.
+    pub methods: Vec<String>,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            delay_ms: 500,
+            max_delay_ms: 10_000,
+            methods: vec![],
+        }
+    }
+}
+
+/// Whether `method` only reads, so sending it twice does no harm.
+pub fn is_idempotent(method: &str) -> bool {
+    method.ends_with(".get") || method.ends_with(".list")
+}
+
+/// Retries idempotent API calls failing with
+/// [transient](SatoriError::is_transient) errors.
+///
+/// Install it with [`Middlewares::api`](crate::middleware::Middlewares::api),
+/// before a [`RateLimiter`](crate::ratelimit::RateLimiter) so that retries are
+/// throttled too.
+#[derive(Debug, Clone, Default)]
+pub struct Retry {
+    config: Arc<RetryConfig>,
+}
+
+impl Retry {
+    pub fn new(config: RetryConfig) -> Self {
+        Self {
+            config: Arc::new(config),
+        }
+    }
+
+    /// Whether calls of `method` may be sent more than once.
+    pub fn retryable(&self, method: &str) -> bool {
+        is_idempotent(method)
+            || self
+                .config
+                .methods
+                .iter()
+                .any(|p| method_matches(p, method))
+    }
+}
+
+impl ApiMiddleware for Retry {
+    async fn call_api(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        next: ApiNext<'_>,
+    ) -> Result<Value, SatoriError> {
+        if !self.retryable(&payload.method) {
+            return next.run(bot, payload).await;
+        }
+        let max_delay = Duration::from_millis(self.config.max_delay_ms);
+        let mut delay = Duration::from_millis(self.config.delay_ms);
+        let mut attempt = 0;
+        loop {
+            let e = match next.run(bot, payload.clone()).await {
+                Err(e) if e.is_transient() && attempt < self.config.retries => e,
+                result => return result,
+            };
+            attempt += 1;
+            let wait = match &e {
+                SatoriError::ApiError(ApiError::RateLimited(Some(after))) => delay.max(*after),
+                _ => delay,
+            };
+            debug!(target: SATORI, ?bot, method = payload.method, attempt, ?wait, "retrying api call: {e}");
+            tokio::time::sleep(wait).await;
+            delay = (delay * 2).min(max_delay);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
+
+    use serde_json::json;
+
+    use super::{Retry, RetryConfig};
+    use crate::{
+        api::RawApiCall,
+        dynamic::BoxFuture,
+        error::{ApiError, SatoriError},
+        middleware::Middlewares,
+        structs::BotId,
+        testing::bot,
+    };
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retry() {
+        let m = Middlewares::new().api(Retry::new(RetryConfig {
+            retries: 2,
+            methods: vec!["message.delete".to_string()],
+            ..Default::default()
+        }));
+        let bot = bot();
+        let call = |method: &str| RawApiCall {
+            method: method.to_string(),
+            body: json!({}),
+        };
+        // fails with `error` until called `ok_at` times
+        let flaky = |ok_at: u32, error: fn() -> SatoriError| {
+            let calls = Arc::new(AtomicU32::new(0));
+            let endpoint = {
+                let calls = calls.clone();
+                move |_: BotId, _: RawApiCall| -> BoxFuture<'static, _> {
+                    let n = calls.fetch_add(1, Ordering::Relaxed) + 1;
+                    Box::pin(async move {
+                        if n < ok_at {
+                            Err(error())
+                        } else {
+                            Ok(json!(n))
+                        }
+                    })
+                }
+            };
+            (calls, endpoint)
+        };
+        let server_error = || ApiError::ServerError(503).into();
+
+        let (_, endpoint) = flaky(3, server_error);
+        let result = m.call_api(&bot, call("guild.get"), &endpoint).await;
+        assert_eq!(result.unwrap(), json!(3));
+
+        let (calls, endpoint) = flaky(4, server_error);
+        let result = m.call_api(&bot, call("message.delete"), &endpoint).await;
+        assert!(matches!(
+            result,
+            Err(SatoriError::ApiError(ApiError::ServerError(503)))
+        ));
+        assert_eq!(calls.load(Ordering::Relaxed), 3);
+
+        let (calls, endpoint) = flaky(2, server_error);
+        assert!(m
+            .call_api(&bot, call("message.create"), &endpoint)
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let (calls, endpoint) = flaky(2, || ApiError::Forbidden.into());
+        assert!(m
+            .call_api(&bot, call("guild.list"), &endpoint)
+            .await
+            .is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+}
@@ -0,0 +1,189 @@
+use std::sync::Arc;
+
+use crate::{filter::Filter, structs::Event, system::SystemEvent, Satori, SatoriApp};
+
+/// An app that only sees events passing `filter`, and system events.
+pub struct Routed<F, A> {
+    filter: F,
+    app: A,
+}
+
+impl<F, A> Routed<F, A> {
+    pub fn new(filter: F, app: A) -> Self {
+        Self { filter, app }
+    }
+}
+
+impl<F, A> SatoriApp for Routed<F, A>
+where
+    F: Filter,
+    A: SatoriApp + Send + Sync,
+{
+    type Error = A::Error;
+
+    async fn start<S>(&self, s: &Arc<S>)
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        self.app.start(s).await
+    }
+
+    async fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> Result<(), A::Error>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        if !self.filter.matches(&event) && SystemEvent::from_event(&event).is_none() {
+            return Ok(());
+        }
+        self.app.handle_event(s, event).await
+    }
+}
+
+/// Hands each event to the first [`Routed`] app whose filter matches, and
+/// system events to all of them.
+///
+/// Routes are a tuple, e.g.
+/// `Router::new((Routed::new(guild("1"), A), Routed::new(any(), B)))`, and
+/// all of them are started. To deliver an event to every matching app, list
+/// the [`Routed`] apps in [`satori!`](crate::satori) instead.
+pub struct Router<T> {
+    routes: T,
+}
+
+impl<T> Router<T> {
+    pub fn new(routes: T) -> Self {
+        Self { routes }
+    }
+}
+
+macro_rules! impl_router {
+    ($( $f:ident $a:ident $i:tt ),+) => {
+        impl<$($f, $a),+> SatoriApp for Router<($(Routed<$f, $a>,)+)>
+        where
+            $(
+                $f: Filter,
+                $a: SatoriApp + Send + Sync,
+            )+
+        {
+            type Error = anyhow::Error;
+
+            async fn start<S>(&self, s: &Arc<S>)
+            where
+                S: Satori + Send + Sync + 'static,
+            {
+                tokio::join!($(self.routes.$i.app.start(s)),+);
+            }
+
+            async fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> anyhow::Result<()>
+            where
+                S: Satori + Send + Sync + 'static,
+            {
+                if SystemEvent::from_event(&event).is_some() {
+                    let mut result = Ok(());
+                    $(
+                        let handled = self.routes.$i.app.handle_event(s, event.clone()).await;
+                        result = result.and(handled.map_err(Into::into));
+                    )+
+                    return result;
+                }
+                $(
+                    let route = &self.routes.$i;
+                    if route.filter.matches(&event) {
+                        return route.app.handle_event(s, event).await.map_err(Into::into);
+                    }
+                )+
+                Ok(())
+            }
+        }
+    };
+}
+
+impl_router!(F0 A0 0);
+impl_router!(F0 A0 0, F1 A1 1);
+impl_router!(F0 A0 0, F1 A1 1, F2 A2 2);
+impl_router!(F0 A0 0, F1 A1 1, F2 A2 2, F3 A3 3);
+impl_router!(F0 A0 0, F1 A1 1, F2 A2 2, F3 A3 3, F4 A4 4);
+impl_router!(F0 A0 0, F1 A1 1, F2 A2 2, F3 A3 3, F4 A4 4, F5 A5 5);
+impl_router!(F0 A0 0, F1 A1 1, F2 A2 2, F3 A3 3, F4 A4 4, F5 A5 5, F6 A6 6);
+impl_router!(F0 A0 0, F1 A1 1, F2 A2 2, F3 A3 3, F4 A4 4, F5 A5 5, F6 A6 6, F7 A7 7);
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::{Arc, Mutex},
+    };
+
+    use super::{Routed, Router};
+    use crate::{
+        dynamic::DynSatori,
+        filter::{any, event_type, group, guild, mention_self, Filter},
+        structs::{Event, Guild, Message},
+        system::SystemEvent,
+        Satori, SatoriApp,
+    };
+
+    struct Record(&'static str, Arc<Mutex<Vec<&'static str>>>);
+
+    impl SatoriApp for Record {
+        type Error = Infallible;
+
+        async fn start<S>(&self, _s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+        }
+
+        async fn handle_event<S>(&self, _s: &Arc<S>, _event: Event) -> Result<(), Infallible>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            self.1.lock().unwrap().push(self.0);
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_router() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let router = Router::new((
+            Routed::new(
+                guild("1").and(mention_self()),
+                Record("mention", seen.clone()),
+            ),
+            Routed::new(
+                group().and(event_type("message-deleted").not()),
+                Record("group", seen.clone()),
+            ),
+            Routed::new(any(), Record("fallback", seen.clone())),
+        ));
+        let s = DynSatori::builder().build();
+
+        let message = |guild: Option<&str>, content: &str| Event {
+            ty: "message-created".to_string(),
+            self_id: "42".to_string(),
+            guild: guild.map(|id| Guild {
+                id: id.to_string(),
+                ..Default::default()
+            }),
+            message: Some(Message {
+                content: Some(content.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        for event in [
+            message(Some("1"), r#"<at id="42"/> hi"#),
+            message(Some("2"), r#"<at id="42"/> hi"#),
+            message(None, "hi"),
+            SystemEvent::ShutdownRequested.into_event(),
+        ] {
+            router.handle_event(&s, event).await.unwrap();
+        }
+
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["mention", "group", "fallback", "mention", "group", "fallback"]
+        );
+    }
+}
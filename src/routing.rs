@@ -0,0 +1,375 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, RwLock,
+    },
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc, time::Instant};
+use tracing::{debug, warn};
+
+use crate::{
+    api::RawApiCall,
+    error::SatoriError,
+    retry::is_idempotent,
+    structs::{BotId, Event, Login},
+    system::SystemEvent,
+    SATORI,
+};
+
+/// How long a bot no SDK claims is remembered, so calls for it fail without
+/// probing every SDK again.
+const UNCLAIMED_TTL: Duration = Duration::from_secs(5);
+
+/// How to pick an SDK when several of them claim the same bot.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RoutePolicy {
+    /// The SDK that claimed the bot first, failing over to later claims.
+    #[default]
+    First,
+    /// The SDK declared first (lowest index), failing over to later ones.
+    Priority,
+    /// Rotate between all SDKs, failing over to the next one on error.
+    RoundRobin,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    pub bot: BotId,
+    /// SDK indices in claim order.
+    pub sdks: Vec<usize>,
+}
+
+#[derive(Debug, Default)]
+struct Entry {
+    sdks: Vec<usize>,
+    next: AtomicUsize,
+}
+
+/// Maps each bot to the SDKs able to serve it.
+///
+/// SDKs are identified by their index in the `sdk` tuple of a
+/// [`satori!`](crate::satori) type, or by [`SdkId`](crate::dynamic::SdkId)
+/// for [`DynSatori`](crate::dynamic::DynSatori).
+#[derive(Debug, Default)]
+pub struct RouteTable {
+    policy: RoutePolicy,
+    routes: RwLock<HashMap<BotId, Entry>>,
+    /// Bots no SDK claimed when probed, until when.
+    unclaimed: Mutex<HashMap<BotId, Instant>>,
+    events: Option<mpsc::UnboundedSender<SystemEvent>>,
+}
+
+impl RouteTable {
+    pub fn new(policy: RoutePolicy) -> Self {
+        Self {
+            policy,
+            ..Default::default()
+        }
+    }
+
+    /// Send [`SystemEvent::BotLogin`] / [`SystemEvent::BotLogout`] to `events`
+    /// when a bot gains its first route or loses its last one.
+    pub fn with_events(mut self, events: mpsc::UnboundedSender<SystemEvent>) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    fn notify(&self, event: SystemEvent) {
+        if let Some(events) = &self.events {
+            events.send(event).ok();
+        }
+    }
+
+    pub fn policy(&self) -> RoutePolicy {
+        self.policy
+    }
+
+    /// SDKs to try for `bot`, best first.
+    pub fn candidates(&self, bot: &BotId) -> Option<Vec<usize>> {
+        let routes = self.routes.read().unwrap();
+        let entry = routes.get(bot)?;
+        let mut sdks = entry.sdks.clone();
+        match self.policy {
+            RoutePolicy::First => {}
+            RoutePolicy::Priority => sdks.sort_unstable(),
+            RoutePolicy::RoundRobin => {
+                let n = entry.next.fetch_add(1, Ordering::Relaxed) % sdks.len();
+                sdks.rotate_left(n);
+            }
+        }
+        Some(sdks)
+    }
+
+    pub fn claim(&self, sdk: usize, bot: &BotId) {
+        let mut routes = self.routes.write().unwrap();
+        self.unclaimed.lock().unwrap().remove(bot);
+        let entry = routes.entry(bot.clone()).or_default();
+        if !entry.sdks.contains(&sdk) {
+            debug!(target: SATORI, ?bot, sdk, "route added");
+            entry.sdks.push(sdk);
+            if entry.sdks.len() == 1 {
+                self.notify(SystemEvent::BotLogin { bot: bot.clone() });
+            }
+        }
+    }
+
+    pub fn claim_logins(&self, sdk: usize, logins: &[Login]) {
+        for login in logins {
+            if let (Some(platform), Some(id)) = (&login.platform, &login.self_id) {
+                self.claim(
+                    sdk,
+                    &BotId {
+                        id: id.clone(),
+                        platform: platform.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn release(&self, sdk: usize, bot: &BotId) {
+        let mut routes = self.routes.write().unwrap();
+        if let Some(entry) = routes.get_mut(bot) {
+            entry.sdks.retain(|s| *s != sdk);
+            debug!(target: SATORI, ?bot, sdk, "route removed");
+            if entry.sdks.is_empty() {
+                routes.remove(bot);
+                self.notify(SystemEvent::BotLogout { bot: bot.clone() });
+            }
+        }
+    }
+
+    /// Replace the SDKs claiming `bot`, keeping the order of existing claims.
+    pub fn set_claims(&self, bot: &BotId, sdks: Vec<usize>) {
+        let mut routes = self.routes.write().unwrap();
+        if sdks.is_empty() {
+            if routes.remove(bot).is_some() {
+                debug!(target: SATORI, ?bot, "route removed");
+                self.notify(SystemEvent::BotLogout { bot: bot.clone() });
+            }
+            return;
+        }
+        self.unclaimed.lock().unwrap().remove(bot);
+        if !routes.contains_key(bot) {
+            self.notify(SystemEvent::BotLogin { bot: bot.clone() });
+        }
+        let entry = routes.entry(bot.clone()).or_default();
+        entry.sdks.retain(|s| sdks.contains(s));
+        for sdk in sdks {
+            if !entry.sdks.contains(&sdk) {
+                entry.sdks.push(sdk);
+            }
+        }
+        debug!(target: SATORI, ?bot, sdks = ?entry.sdks, "route updated");
+    }
+
+    pub fn remove_sdk(&self, sdk: usize) {
+        let mut routes = self.routes.write().unwrap();
+        routes.retain(|bot, entry| {
+            entry.sdks.retain(|s| *s != sdk);
+            if entry.sdks.is_empty() {
+                self.notify(SystemEvent::BotLogout { bot: bot.clone() });
+            }
+            !entry.sdks.is_empty()
+        });
+    }
+
+    pub fn routes(&self) -> Vec<Route> {
+        let routes = self.routes.read().unwrap();
+        routes
+            .iter()
+            .map(|(bot, entry)| Route {
+                bot: bot.clone(),
+                sdks: entry.sdks.clone(),
+            })
+            .collect()
+    }
+
+    fn is_unclaimed(&self, bot: &BotId) -> bool {
+        let unclaimed = self.unclaimed.lock().unwrap();
+        unclaimed
+            .get(bot)
+            .is_some_and(|until| *until > Instant::now())
+    }
+
+    fn mark_unclaimed(&self, bot: &BotId) {
+        let now = Instant::now();
+        let mut unclaimed = self.unclaimed.lock().unwrap();
+        unclaimed.retain(|_, until| *until > now);
+        unclaimed.insert(bot.clone(), now + UNCLAIMED_TTL);
+    }
+
+    /// Route an API call to an SDK.
+    ///
+    /// `probe` lists the SDKs claiming the bot and is only called when the
+    /// bot has no route yet. A bot no SDK claims fails with
+    /// [`SatoriError::InvalidBot`] without probing again for a few seconds.
+    pub async fn call<P, PF, C, CF, T>(
+        &self,
+        bot: &BotId,
+        payload: RawApiCall,
+        probe: P,
+        call: C,
+    ) -> Result<T, SatoriError>
+    where
+        P: FnOnce() -> PF,
+        PF: Future<Output = Vec<usize>>,
+        C: Fn(usize, RawApiCall) -> CF,
+        CF: Future<Output = Result<T, SatoriError>>,
+    {
+        let candidates = match self.candidates(bot) {
+            Some(candidates) => candidates,
+            None if self.is_unclaimed(bot) => return Err(SatoriError::InvalidBot),
+            None => {
+                let sdks = probe().await;
+                if sdks.is_empty() {
+                    self.mark_unclaimed(bot);
+                }
+                self.set_claims(bot, sdks);
+                self.candidates(bot).ok_or(SatoriError::InvalidBot)?
+            }
+        };
+        // other errors may come after the call went through, so only reads
+        // are safe to send again
+        let idempotent = is_idempotent(&payload.method);
+        let (last, rest) = candidates.split_last().ok_or(SatoriError::InvalidBot)?;
+        for &sdk in rest {
+            match call(sdk, payload.clone()).await {
+                Err(SatoriError::InvalidBot) => {
+                    warn!(target: SATORI, ?bot, sdk, "bot not served, failing over");
+                    self.release(sdk, bot);
+                }
+                Err(e @ SatoriError::InternalError(_)) if idempotent => {
+                    warn!(target: SATORI, ?bot, sdk, "api call failed, failing over: {e}");
+                }
+                result => return result,
+            }
+        }
+        let result = call(*last, payload).await;
+        if matches!(result, Err(SatoriError::InvalidBot)) {
+            self.release(*last, bot);
+        }
+        result
+    }
+}
+
+/// The bot whose login changed, for `login-*` events.
+pub fn login_bot(event: &Event) -> Option<BotId> {
+    matches!(
+        event.ty.as_str(),
+        "login-added" | "login-removed" | "login-updated"
+    )
+    .then(|| BotId {
+        id: event.self_id.clone(),
+        platform: event.platform.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicU32, Ordering},
+        time::Duration,
+    };
+
+    use serde_json::json;
+
+    use super::{RoutePolicy, RouteTable};
+    use crate::{api::RawApiCall, error::SatoriError, structs::BotId, testing::bot};
+
+    #[test]
+    fn test_policy() {
+        let first = RouteTable::new(RoutePolicy::First);
+        let priority = RouteTable::new(RoutePolicy::Priority);
+        let round_robin = RouteTable::new(RoutePolicy::RoundRobin);
+        for table in [&first, &priority, &round_robin] {
+            table.claim(2, &bot());
+            table.claim(0, &bot());
+            table.claim(1, &bot());
+        }
+
+        assert_eq!(first.candidates(&bot()), Some(vec![2, 0, 1]));
+        assert_eq!(priority.candidates(&bot()), Some(vec![0, 1, 2]));
+        assert_eq!(round_robin.candidates(&bot()), Some(vec![2, 0, 1]));
+        assert_eq!(round_robin.candidates(&bot()), Some(vec![0, 1, 2]));
+
+        first.set_claims(&bot(), vec![1, 2, 3]);
+        assert_eq!(first.routes()[0].sdks, vec![2, 1, 3]);
+        first.remove_sdk(2);
+        assert_eq!(first.candidates(&bot()), Some(vec![1, 3]));
+        first.set_claims(&bot(), vec![]);
+        assert_eq!(first.candidates(&bot()), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_failover() {
+        let table = RouteTable::new(RoutePolicy::RoundRobin);
+        let payload = RawApiCall {
+            method: "test".to_string(),
+            body: json!(null),
+        };
+        let call = |sdk, _| async move {
+            match sdk {
+                0 => Err(SatoriError::InvalidBot),
+                _ => Ok(json!(sdk)),
+            }
+        };
+
+        let result = table
+            .call(&bot(), payload.clone(), || async { vec![0, 1] }, call)
+            .await;
+        assert_eq!(result.unwrap(), json!(1));
+        assert_eq!(table.routes()[0].sdks, vec![1]);
+
+        let unknown = BotId {
+            id: "2".to_string(),
+            platform: "test".to_string(),
+        };
+        let probes = AtomicU32::new(0);
+        let probe = || async {
+            probes.fetch_add(1, Ordering::Relaxed);
+            vec![]
+        };
+        for _ in 0..2 {
+            let result = table.call(&unknown, payload.clone(), probe, call).await;
+            assert!(matches!(result, Err(SatoriError::InvalidBot)));
+        }
+        assert_eq!(probes.load(Ordering::Relaxed), 1);
+        tokio::time::advance(Duration::from_secs(6)).await;
+        let result = table.call(&unknown, payload.clone(), probe, call).await;
+        assert!(matches!(result, Err(SatoriError::InvalidBot)));
+        assert_eq!(probes.load(Ordering::Relaxed), 2);
+
+        // the first claim is only preferred while it works
+        let table = RouteTable::new(RoutePolicy::First);
+        table.claim(2, &bot());
+        table.claim(1, &bot());
+        let flaky = |sdk, _| async move {
+            match sdk {
+                2 => Err(SatoriError::InternalError(anyhow::anyhow!("down"))),
+                _ => Ok(json!(sdk)),
+            }
+        };
+        let get = RawApiCall {
+            method: "guild.get".to_string(),
+            body: json!(null),
+        };
+        let result = table.call(&bot(), get, || async { vec![] }, flaky).await;
+        assert_eq!(result.unwrap(), json!(1));
+        assert_eq!(table.routes()[0].sdks, vec![2, 1]);
+
+        // a call that failed may still have gone through
+        let create = RawApiCall {
+            method: "message.create".to_string(),
+            body: json!(null),
+        };
+        let result = table.call(&bot(), create, || async { vec![] }, flaky).await;
+        assert!(matches!(result, Err(SatoriError::InternalError(_))));
+    }
+}
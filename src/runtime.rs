@@ -0,0 +1,400 @@
+use std::{
+    collections::HashMap,
+    future::{poll_fn, Future},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use tokio::{
+    sync::{mpsc, watch},
+    task::{AbortHandle, Id, JoinError, JoinSet},
+    time::Instant,
+};
+use tokio_util::{
+    sync::CancellationToken,
+    task::{task_tracker::TrackedFuture, TaskTracker},
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    dedup::Dedup,
+    dispatch::{DispatchOptions, Dispatcher},
+    middleware::Middlewares,
+    report::ErrorHook,
+    routing::{RoutePolicy, RouteTable},
+    session::Waiters,
+    structs::Event,
+    system::{is_internal, SystemEvent},
+    upload::{self, UploadStore},
+    SATORI,
+};
+
+#[derive(Debug)]
+pub struct SatoriOptions {
+    pub middlewares: Middlewares,
+    pub route_policy: RoutePolicy,
+    pub dispatch: DispatchOptions,
+    pub error_hooks: Vec<ErrorHook>,
+    /// How long shutdown waits for handlers, API calls and SDKs to finish
+    /// before aborting them.
+    pub shutdown_timeout: Duration,
+    /// Where uploads go when no SDK handles them; in memory if `None`. See
+    /// [`UploadStore`].
+    pub upload_dir: Option<PathBuf>,
+    /// How many bytes of uploads are kept in memory without an
+    /// [`upload_dir`](Self::upload_dir).
+    pub upload_memory_bytes: usize,
+    /// How long events are remembered to drop duplicates, e.g. from
+    /// redundant SDKs; off if `None`. See [`Dedup`].
+    pub dedup_window: Option<Duration>,
+}
+
+impl Default for SatoriOptions {
+    fn default() -> Self {
+        Self {
+            middlewares: Default::default(),
+            route_policy: Default::default(),
+            dispatch: Default::default(),
+            error_hooks: vec![ErrorHook::Log],
+            shutdown_timeout: Duration::from_secs(10),
+            upload_dir: None,
+            upload_memory_bytes: upload::DEFAULT_MEMORY_BYTES,
+            dedup_window: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LifecycleState {
+    /// Not started yet.
+    #[default]
+    Idle,
+    Starting,
+    Running,
+    /// No new events are accepted, in-flight work is draining.
+    Stopping,
+    Stopped,
+}
+
+impl LifecycleState {
+    /// Whether SDK and app tasks have been (or are being) spawned.
+    pub fn is_started(self) -> bool {
+        matches!(self, Self::Starting | Self::Running)
+    }
+}
+
+#[derive(Debug, Default)]
+struct Tasks {
+    set: JoinSet<()>,
+    names: HashMap<Id, String>,
+}
+
+#[derive(Debug)]
+pub struct Runtime {
+    options: SatoriOptions,
+    routes: RouteTable,
+    dispatchers: Mutex<HashMap<usize, Arc<Dispatcher>>>,
+    waiters: Waiters,
+    uploads: UploadStore,
+    dedup: Dedup,
+    state: watch::Sender<LifecycleState>,
+    in_flight: TaskTracker,
+    stop: CancellationToken,
+    tasks: Mutex<Tasks>,
+    system: mpsc::UnboundedSender<SystemEvent>,
+    system_rx: Mutex<Option<mpsc::UnboundedReceiver<SystemEvent>>>,
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl Runtime {
+    pub fn new(options: SatoriOptions) -> Self {
+        let (system, system_rx) = mpsc::unbounded_channel();
+        Self {
+            routes: RouteTable::new(options.route_policy).with_events(system.clone()),
+            uploads: UploadStore::new(options.upload_dir.clone(), options.upload_memory_bytes),
+            dedup: Dedup::new(options.dedup_window),
+            options,
+            dispatchers: Default::default(),
+            waiters: Default::default(),
+            state: watch::channel(LifecycleState::Idle).0,
+            in_flight: TaskTracker::new(),
+            stop: CancellationToken::new(),
+            tasks: Default::default(),
+            system,
+            system_rx: Mutex::new(Some(system_rx)),
+        }
+    }
+
+    pub fn middlewares(&self) -> &Middlewares {
+        &self.options.middlewares
+    }
+
+    pub fn routes(&self) -> &RouteTable {
+        &self.routes
+    }
+
+    pub fn error_hooks(&self) -> &[ErrorHook] {
+        &self.options.error_hooks
+    }
+
+    /// The dispatcher for the app at `index`.
+    pub fn dispatcher(&self, index: usize) -> Arc<Dispatcher> {
+        self.dispatchers
+            .lock()
+            .unwrap()
+            .entry(index)
+            .or_insert_with(|| Arc::new(Dispatcher::new(self.options.dispatch)))
+            .clone()
+    }
+
+    pub fn remove_dispatcher(&self, index: usize) {
+        self.dispatchers.lock().unwrap().remove(&index);
+    }
+
+    pub fn waiters(&self) -> &Waiters {
+        &self.waiters
+    }
+
+    pub fn uploads(&self) -> &UploadStore {
+        &self.uploads
+    }
+
+    pub fn dedup(&self) -> &Dedup {
+        &self.dedup
+    }
+
+    pub fn state(&self) -> LifecycleState {
+        *self.state.borrow()
+    }
+
+    /// Wait until the runtime reaches `state` (or a later one).
+    pub async fn wait_for(&self, state: LifecycleState) {
+        let mut rx = self.state.subscribe();
+        let _ = rx.wait_for(|s| *s >= state).await;
+    }
+
+    fn transition(&self, from: &[LifecycleState], to: LifecycleState) -> bool {
+        self.state.send_if_modified(|state| {
+            if !from.contains(state) {
+                return false;
+            }
+            debug!(target: SATORI, from = ?*state, ?to, "lifecycle");
+            *state = to;
+            true
+        })
+    }
+
+    /// Move from `Idle` to `Starting`; `false` if already started.
+    pub fn begin_start(&self) -> bool {
+        let started = self.transition(&[LifecycleState::Idle], LifecycleState::Starting);
+        if started {
+            info!(target: SATORI, "Starting...");
+        } else {
+            warn!(target: SATORI, state = ?self.state(), "already started");
+        }
+        started
+    }
+
+    pub fn finish_start(&self) {
+        self.transition(&[LifecycleState::Starting], LifecycleState::Running);
+    }
+
+    /// Whether `event` should be handed to apps.
+    ///
+    /// While stopping, only internal events are accepted.
+    pub fn accepts(&self, event: &Event) -> bool {
+        match self.state() {
+            LifecycleState::Starting | LifecycleState::Running => true,
+            LifecycleState::Stopping => is_internal(event),
+            _ => false,
+        }
+    }
+
+    /// Queue a system event, delivered to apps once started.
+    pub fn emit(&self, event: SystemEvent) {
+        self.system.send(event).ok();
+    }
+
+    /// The receiving end of [`emit`](Self::emit), taken once when starting.
+    pub fn take_system_events(&self) -> Option<mpsc::UnboundedReceiver<SystemEvent>> {
+        self.system_rx.lock().unwrap().take()
+    }
+
+    /// Keep shutdown waiting for `task` (an event handler or API call).
+    pub fn track<F>(&self, task: F) -> TrackedFuture<F>
+    where
+        F: Future,
+    {
+        self.in_flight.track_future(task)
+    }
+
+    pub fn spawn<F>(&self, name: impl Into<String>, task: F) -> AbortHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        let mut tasks = self.tasks.lock().unwrap();
+        let handle = tasks.set.spawn(task);
+        tasks.names.insert(handle.id(), name.into());
+        handle
+    }
+
+    fn poll_task(&self, cx: &mut std::task::Context<'_>) -> std::task::Poll<bool> {
+        let mut tasks = self.tasks.lock().unwrap();
+        let Some(result) = std::task::ready!(tasks.set.poll_join_next_with_id(cx)) else {
+            return std::task::Poll::Ready(false);
+        };
+        let (id, error) = match result {
+            Ok((id, ())) => (id, None),
+            Err(e) => (e.id(), Some(e)),
+        };
+        let name = tasks.names.remove(&id).unwrap_or_default();
+        drop(tasks);
+        self.task_finished(&name, error);
+        std::task::Poll::Ready(true)
+    }
+
+    fn task_finished(&self, name: &str, error: Option<JoinError>) {
+        match error {
+            Some(e) if e.is_panic() => {
+                error!(target: SATORI, task = name, "task panicked: {e}")
+            }
+            Some(_) => debug!(target: SATORI, task = name, "task aborted"),
+            None if self.stop.is_cancelled() => debug!(target: SATORI, task = name, "task stopped"),
+            None => warn!(target: SATORI, task = name, "task exited before shutdown"),
+        }
+    }
+
+    /// Wait until every spawned task has finished, reporting failures.
+    ///
+    /// The task set is only locked while being polled, so tasks can still be
+    /// spawned (or the runtime shut down) while this is pending.
+    pub async fn join(&self) {
+        while poll_fn(|cx| self.poll_task(cx)).await {}
+    }
+
+    /// Stop gracefully, in order:
+    ///
+    /// 1. stop handing new platform events to apps, run `announce` (which
+    ///    usually delivers [`SystemEvent::ShutdownRequested`]);
+    /// 2. wait for in-flight event handlers and API calls;
+    /// 3. signal [`stopped`](Self::stopped) so SDKs and apps close their
+    ///    connections, and wait for their tasks;
+    /// 4. abort whatever is left once the shutdown timeout expires.
+    ///
+    /// Concurrent calls wait for the first one to finish. Handlers and API
+    /// calls must spawn it rather than await it, or it waits on them until
+    /// the timeout.
+    pub async fn shutdown<F>(&self, announce: F)
+    where
+        F: Future<Output = ()>,
+    {
+        let from = [
+            LifecycleState::Idle,
+            LifecycleState::Starting,
+            LifecycleState::Running,
+        ];
+        if !self.transition(&from, LifecycleState::Stopping) {
+            return self.wait_for(LifecycleState::Stopped).await;
+        }
+        info!(target: SATORI, "Stopping...");
+        let deadline = Instant::now() + self.options.shutdown_timeout;
+
+        if tokio::time::timeout_at(deadline, announce).await.is_err() {
+            warn!(target: SATORI, "shutdown announcement did not finish in time");
+        }
+        self.waiters.cancel_all();
+        self.in_flight.close();
+        if tokio::time::timeout_at(deadline, self.in_flight.wait())
+            .await
+            .is_err()
+        {
+            warn!(
+                target: SATORI,
+                count = self.in_flight.len(),
+                "in-flight work did not finish in time"
+            );
+        }
+
+        self.stop.cancel();
+        if tokio::time::timeout_at(deadline, self.join())
+            .await
+            .is_err()
+        {
+            let names = {
+                let mut tasks = self.tasks.lock().unwrap();
+                tasks.set.abort_all();
+                tasks.names.values().cloned().collect::<Vec<_>>()
+            };
+            warn!(target: SATORI, tasks = ?names, "tasks did not stop in time, aborted");
+            self.join().await;
+        }
+
+        self.transition(&[LifecycleState::Stopping], LifecycleState::Stopped);
+        info!(target: SATORI, "Stopped");
+    }
+
+    /// Resolves once shutdown has drained in-flight work; SDKs and apps
+    /// should close their connections and return.
+    pub async fn stopped(&self) {
+        self.stop.cancelled().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        time::Duration,
+    };
+
+    use super::{LifecycleState, Runtime, SatoriOptions};
+
+    #[tokio::test]
+    async fn test_shutdown_order() {
+        let runtime = Arc::new(Runtime::new(SatoriOptions {
+            shutdown_timeout: Duration::from_millis(200),
+            ..Default::default()
+        }));
+        assert!(runtime.begin_start());
+        assert!(!runtime.begin_start());
+        runtime.finish_start();
+        assert_eq!(runtime.state(), LifecycleState::Running);
+
+        let drained = Arc::new(AtomicBool::new(false));
+        let drained_first = Arc::new(AtomicBool::new(false));
+        tokio::spawn(runtime.track({
+            let drained = drained.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                drained.store(true, Ordering::SeqCst);
+            }
+        }));
+        runtime.spawn("sdk", {
+            let (runtime, drained) = (runtime.clone(), drained.clone());
+            let drained_first = drained_first.clone();
+            async move {
+                runtime.stopped().await;
+                drained_first.store(drained.load(Ordering::SeqCst), Ordering::SeqCst);
+            }
+        });
+        let stuck = runtime.spawn("stuck", std::future::pending());
+
+        runtime.shutdown(async {}).await;
+        assert_eq!(runtime.state(), LifecycleState::Stopped);
+        assert!(drained_first.load(Ordering::SeqCst));
+        assert!(!runtime.accepts(&Default::default()));
+        assert!(stuck.is_finished());
+        assert!(runtime.tasks.lock().unwrap().set.is_empty());
+    }
+}
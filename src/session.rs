@@ -0,0 +1,538 @@
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use serde_json::Value;
+use tokio::sync::oneshot;
+use tracing::debug;
+
+use crate::{
+    api::SatoriApi,
+    error::SessionError,
+    runtime::LifecycleState,
+    structs::{BotId, Channel, ChannelType, Event, Guild, Message, User},
+    Satori, SATORI,
+};
+
+/// Who a waiting session expects a message from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct WaitKey {
+    platform: String,
+    self_id: String,
+    channel_id: String,
+    user_id: String,
+}
+
+impl WaitKey {
+    fn of(event: &Event) -> Option<Self> {
+        let message = event.message.as_ref();
+        let channel = event
+            .channel
+            .as_ref()
+            .or(message.and_then(|m| m.channel.as_ref()))?;
+        let user = event
+            .user
+            .as_ref()
+            .or(message.and_then(|m| m.user.as_ref()))?;
+        Some(Self {
+            platform: event.platform.clone(),
+            self_id: event.self_id.clone(),
+            channel_id: channel.id.clone(),
+            user_id: user.id.clone(),
+        })
+    }
+}
+
+tokio::task_local! {
+    /// Index of the app whose handler is running.
+    static APP: usize;
+}
+
+/// Run `f` as a handler of the app at `index`, so that sessions waiting in
+/// it are known to belong to that app.
+#[doc(hidden)]
+pub async fn in_app<F: Future>(index: usize, f: F) -> F::Output {
+    APP.scope(index, f).await
+}
+
+#[derive(Debug)]
+struct Waiter {
+    id: u64,
+    key: WaitKey,
+    app: Option<usize>,
+    tx: oneshot::Sender<Event>,
+}
+
+/// The session an event was handed to, see [`Waiters::offer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Taken {
+    /// The app waiting in the session; unknown if it waits outside of a
+    /// handler, e.g. in a task the handler spawned.
+    pub app: Option<usize>,
+}
+
+impl Taken {
+    /// Whether the app at `index` must not see the event, either because it
+    /// already has it or because the app that has it is unknown.
+    pub fn skips(&self, index: usize) -> bool {
+        self.app.is_none_or(|app| app == index)
+    }
+}
+
+/// Sessions waiting for their next message.
+#[derive(Debug, Default)]
+pub struct Waiters {
+    next: AtomicU64,
+    waiters: Mutex<Vec<Waiter>>,
+}
+
+impl Waiters {
+    fn register(&self, key: WaitKey) -> (u64, oneshot::Receiver<Event>) {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        let (tx, rx) = oneshot::channel();
+        let app = APP.try_with(|app| *app).ok();
+        let waiter = Waiter { id, key, app, tx };
+        self.waiters.lock().unwrap().push(waiter);
+        (id, rx)
+    }
+
+    fn remove(&self, id: u64) {
+        self.waiters.lock().unwrap().retain(|w| w.id != id);
+    }
+
+    /// Hand a `message-created` event to the oldest session waiting for it.
+    ///
+    /// Returns `None` if no session took it. Otherwise the event must not be
+    /// dispatched to the apps the returned [`Taken`] skips; other apps, such
+    /// as a [`NetApp`](crate::impls::net::app::NetApp)'s clients, still
+    /// receive it.
+    pub fn offer(&self, event: &Event) -> Option<Taken> {
+        if event.ty != "message-created" {
+            return None;
+        }
+        let key = WaitKey::of(event)?;
+        let mut waiters = self.waiters.lock().unwrap();
+        while let Some(pos) = waiters.iter().position(|w| w.key == key) {
+            let waiter = waiters.remove(pos);
+            // a dropped session fails the send, try the next one
+            if waiter.tx.send(event.clone()).is_ok() {
+                debug!(target: SATORI, ?key, app = waiter.app, "event taken by session");
+                return Some(Taken { app: waiter.app });
+            }
+        }
+        None
+    }
+
+    /// Wake every waiting session with [`SessionError::Cancelled`].
+    pub fn cancel_all(&self) {
+        self.waiters.lock().unwrap().clear();
+    }
+}
+
+/// Removes the waiter when the waiting future is dropped.
+struct WaitGuard<'a> {
+    waiters: &'a Waiters,
+    id: u64,
+}
+
+impl Drop for WaitGuard<'_> {
+    fn drop(&mut self) {
+        self.waiters.remove(self.id);
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// An event together with the [`Satori`] it came from.
+///
+/// Accessors fail with [`SessionError::MissingField`] when the event lacks
+/// the field, e.g. a `guild-updated` event has no message to reply to.
+pub struct Session<S> {
+    s: Arc<S>,
+    event: Event,
+}
+
+impl<S> Clone for Session<S> {
+    fn clone(&self) -> Self {
+        Self {
+            s: self.s.clone(),
+            event: self.event.clone(),
+        }
+    }
+}
+
+impl<S> Session<S>
+where
+    S: Satori + Send + Sync + 'static,
+{
+    pub fn new(s: &Arc<S>, event: Event) -> Self {
+        Self {
+            s: s.clone(),
+            event,
+        }
+    }
+
+    pub fn satori(&self) -> &Arc<S> {
+        &self.s
+    }
+
+    pub fn event(&self) -> &Event {
+        &self.event
+    }
+
+    pub fn bot(&self) -> BotId {
+        BotId {
+            id: self.event.self_id.clone(),
+            platform: self.event.platform.clone(),
+        }
+    }
+
+    pub fn message(&self) -> Result<&Message, SessionError> {
+        self.event
+            .message
+            .as_ref()
+            .ok_or(SessionError::MissingField("message"))
+    }
+
+    /// Text of the message, empty if it has none.
+    pub fn content(&self) -> &str {
+        self.message()
+            .ok()
+            .and_then(|m| m.content.as_deref())
+            .unwrap_or_default()
+    }
+
+    pub fn channel(&self) -> Result<&Channel, SessionError> {
+        let message = self.event.message.as_ref();
+        self.event
+            .channel
+            .as_ref()
+            .or(message.and_then(|m| m.channel.as_ref()))
+            .ok_or(SessionError::MissingField("channel"))
+    }
+
+    pub fn channel_id(&self) -> Result<&str, SessionError> {
+        Ok(&self.channel()?.id)
+    }
+
+    pub fn user(&self) -> Result<&User, SessionError> {
+        let message = self.event.message.as_ref();
+        self.event
+            .user
+            .as_ref()
+            .or(message.and_then(|m| m.user.as_ref()))
+            .ok_or(SessionError::MissingField("user"))
+    }
+
+    pub fn user_id(&self) -> Result<&str, SessionError> {
+        Ok(&self.user()?.id)
+    }
+
+    pub fn guild(&self) -> Result<&Guild, SessionError> {
+        let message = self.event.message.as_ref();
+        self.event
+            .guild
+            .as_ref()
+            .or(message.and_then(|m| m.guild.as_ref()))
+            .ok_or(SessionError::MissingField("guild"))
+    }
+
+    /// Whether the event happened in a private chat.
+    pub fn is_direct(&self) -> bool {
+        let direct = self
+            .channel()
+            .is_ok_and(|c| matches!(c.ty, Some(ChannelType::Direct)));
+        direct || self.guild().is_err()
+    }
+
+    /// Send a message to the channel the event came from.
+    pub async fn send(&self, content: impl Into<String>) -> Result<Value, SessionError> {
+        let channel_id = self.channel_id()?.to_string();
+        Ok(self
+            .s
+            .create_message(&self.bot(), channel_id, content.into())
+            .await?)
+    }
+
+    /// Answer the user, mentioning them outside of private chats.
+    pub async fn reply(&self, content: impl Into<String>) -> Result<Value, SessionError> {
+        let content = content.into();
+        match self.user() {
+            Ok(user) if !self.is_direct() => {
+                let mention = format!(r#"<at id="{}"/> "#, escape(&user.id));
+                self.send(mention + &content).await
+            }
+            _ => self.send(content).await,
+        }
+    }
+
+    /// Answer quoting the message of the event.
+    pub async fn quote_reply(&self, content: impl Into<String>) -> Result<Value, SessionError> {
+        let quote = format!(r#"<quote id="{}"/>"#, escape(&self.message()?.id));
+        self.send(quote + &content.into()).await
+    }
+
+    /// Delete the message of the event.
+    pub async fn delete(&self) -> Result<Value, SessionError> {
+        let message_id = self.message()?.id.clone();
+        let channel_id = self.channel_id()?.to_string();
+        Ok(self
+            .s
+            .delete_message(&self.bot(), channel_id, message_id)
+            .await?)
+    }
+
+    /// React to the message of the event.
+    pub async fn react(&self, emoji: impl Into<String>) -> Result<Value, SessionError> {
+        let message_id = self.message()?.id.clone();
+        let channel_id = self.channel_id()?.to_string();
+        Ok(self
+            .s
+            .create_reaction(&self.bot(), channel_id, message_id, emoji.into())
+            .await?)
+    }
+
+    /// Start waiting for the next message, see [`Session::next_message`].
+    fn wait(&self) -> Result<(WaitGuard<'_>, oneshot::Receiver<Event>), SessionError> {
+        let key = WaitKey::of(&self.event).ok_or(SessionError::MissingField("user"))?;
+        let runtime = self.s.runtime();
+        if runtime.state() >= LifecycleState::Stopping {
+            return Err(SessionError::Cancelled);
+        }
+        let waiters = runtime.waiters();
+        let (id, rx) = waiters.register(key);
+        Ok((WaitGuard { waiters, id }, rx))
+    }
+
+    async fn receive(
+        &self,
+        rx: oneshot::Receiver<Event>,
+        timeout: Duration,
+    ) -> Result<Event, SessionError> {
+        tokio::select! {
+            event = rx => event.map_err(|_| SessionError::Cancelled),
+            _ = tokio::time::sleep(timeout) => Err(SessionError::Timeout),
+            _ = self.s.stopped() => Err(SessionError::Cancelled),
+        }
+    }
+
+    /// Wait for the next message from the same user in the same channel.
+    ///
+    /// The message is not dispatched to the app waiting for it; other apps
+    /// still receive it, unless the session waits outside of the app's
+    /// handler. Fails with [`SessionError::Timeout`] after `timeout`, or
+    /// [`SessionError::Cancelled`] when shutting down. Dropping the future
+    /// stops waiting.
+    pub async fn next_message(&self, timeout: Duration) -> Result<Event, SessionError> {
+        let (_guard, rx) = self.wait()?;
+        self.receive(rx, timeout).await
+    }
+
+    /// Reply with `content`, then wait for the answer and return its text.
+    ///
+    /// Waiting starts before the reply is sent, so even an immediate answer
+    /// is caught.
+    pub async fn prompt(
+        &self,
+        content: impl Into<String>,
+        timeout: Duration,
+    ) -> Result<String, SessionError> {
+        let (_guard, rx) = self.wait()?;
+        self.reply(content).await?;
+        let event = self.receive(rx, timeout).await?;
+        Ok(Session::new(&self.s, event).content().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use serde_json::{json, Value};
+
+    use super::Session;
+    use crate::{
+        api::RawApiCall,
+        dynamic::DynSatori,
+        error::{SatoriError, SessionError},
+        structs::{BotId, Event, Guild, Login},
+        testing::{message, Sink},
+        Satori, SatoriApp, SatoriSDK,
+    };
+
+    struct Greeter;
+
+    impl SatoriApp for Greeter {
+        type Error = SessionError;
+
+        async fn start<S>(&self, _s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+        }
+
+        async fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> Result<(), SessionError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            let session = Session::new(s, event);
+            match session.prompt("name?", Duration::from_millis(100)).await {
+                Ok(name) => session.reply(format!("hi {name}")).await?,
+                Err(SessionError::Timeout) => session.reply("timeout").await?,
+                Err(e) => return Err(e),
+            };
+            Ok(())
+        }
+    }
+
+    /// Records the text of every message it sees.
+    struct Seen(Arc<Mutex<Vec<String>>>);
+
+    impl SatoriApp for Seen {
+        type Error = SessionError;
+
+        async fn start<S>(&self, _s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+        }
+
+        async fn handle_event<S>(&self, s: &Arc<S>, event: Event) -> Result<(), SessionError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            if event.ty == "message-created" {
+                let content = Session::new(s, event).content().to_string();
+                self.0.lock().unwrap().push(content);
+            }
+            Ok(())
+        }
+    }
+
+    /// Answers every prompt before the prompting call returns.
+    struct Eager(Sink);
+
+    impl SatoriSDK for Eager {
+        async fn start<S>(&self, _s: &Arc<S>)
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+        }
+
+        async fn call_api<S>(
+            &self,
+            s: &Arc<S>,
+            bot: &BotId,
+            payload: RawApiCall,
+        ) -> Result<Value, SatoriError>
+        where
+            S: Satori + Send + Sync + 'static,
+        {
+            if payload.body["content"] == "name?" {
+                s.handle_event(message("a", "bob")).await;
+            }
+            self.0.call_api(s, bot, payload).await
+        }
+
+        async fn has_bot(&self, _bot: &BotId) -> bool {
+            true
+        }
+
+        async fn get_logins(&self) -> Vec<Login> {
+            vec![]
+        }
+    }
+
+    #[tokio::test]
+    async fn test_prompt() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let seen = Arc::new(Mutex::new(vec![]));
+        let s = DynSatori::builder()
+            .sdk(Sink(sent.clone()))
+            .app(Greeter)
+            .app(Seen(seen.clone()))
+            .build();
+        s.spawn().await;
+
+        s.handle_event(message("a", "hello")).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        s.handle_event(message("a", "bob")).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(*sent.lock().unwrap(), vec![json!("name?"), json!("hi bob")]);
+        // the answer is only kept from the app that asked for it
+        assert_eq!(*seen.lock().unwrap(), vec!["hello", "bob"]);
+
+        sent.lock().unwrap().clear();
+        s.handle_event(message("a", "hello")).await;
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![json!("name?"), json!("timeout")]
+        );
+
+        s.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_prompt_answered_at_once() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let s = DynSatori::builder()
+            .sdk(Eager(Sink(sent.clone())))
+            .app(Greeter)
+            .build();
+        s.spawn().await;
+
+        s.handle_event(message("a", "hello")).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(*sent.lock().unwrap(), vec![json!("name?"), json!("hi bob")]);
+
+        s.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_fields() {
+        let sent = Arc::new(Mutex::new(vec![]));
+        let s = DynSatori::builder().sdk(Sink(sent.clone())).build();
+        s.spawn().await;
+
+        let mut event = message("a", "hi");
+        event.message.as_mut().unwrap().id = "m".to_string();
+        event.guild = Some(Guild {
+            id: "g".to_string(),
+            ..Default::default()
+        });
+        let session = Session::new(&s, event);
+        assert!(!session.is_direct());
+        session.reply("yes").await.unwrap();
+        session.quote_reply("no").await.unwrap();
+        assert_eq!(
+            *sent.lock().unwrap(),
+            vec![json!(r#"<at id="a"/> yes"#), json!(r#"<quote id="m"/>no"#)]
+        );
+
+        let session = Session::new(&s, Event::default());
+        assert!(matches!(
+            session.reply("x").await,
+            Err(SessionError::MissingField("channel"))
+        ));
+        assert!(matches!(
+            session.delete().await,
+            Err(SessionError::MissingField("message"))
+        ));
+
+        s.shutdown().await;
+    }
+}
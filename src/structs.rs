@@ -1,8 +1,9 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct BotId {
     pub id: String,
     pub platform: String,
@@ -24,6 +25,21 @@ pub struct Event {
     pub operator: Option<User>,
     pub role: Option<GuildRole>,
     pub user: Option<User>,
+    pub argv: Option<Argv>,
+    #[serde(rename = "_type")]
+    pub internal_type: Option<String>,
+    #[serde(rename = "_data")]
+    pub internal_data: Option<Value>,
+}
+
+/// Arguments of an `interaction/command` event.
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+pub struct Argv {
+    pub name: String,
+    #[serde(default)]
+    pub arguments: Vec<Value>,
+    #[serde(default)]
+    pub options: serde_json::Map<String, Value>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
@@ -58,6 +74,7 @@ pub struct Login {
     pub self_id: Option<String>,
     pub platform: Option<String>,
     pub status: Status,
+    pub features: Option<Vec<String>>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
@@ -86,6 +103,7 @@ pub struct GuildMember {
     pub nick: Option<String>,
     pub avatar: Option<String>,
     pub joined_at: Option<i64>,
+    pub roles: Option<Vec<String>>,
 }
 
 #[derive(Debug, Default, Clone, Deserialize, Serialize)]
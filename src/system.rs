@@ -0,0 +1,157 @@
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::{
+    structs::{BotId, Event},
+    Satori, SATORI,
+};
+
+/// [`Event::ty`] of events emitted by Satori itself; the actual type is in
+/// [`Event::internal_type`].
+pub const INTERNAL: &str = "internal";
+pub const APP_ERROR: &str = "satori/app-error";
+
+/// Events emitted by Satori itself, delivered to apps through
+/// [`SatoriApp::handle_event`](crate::SatoriApp::handle_event) as
+/// [`INTERNAL`] events.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "_type", content = "_data")]
+pub enum SystemEvent {
+    /// An SDK connected to its platform (or upstream Satori server).
+    #[serde(rename = "satori/sdk-connected")]
+    SdkConnected { sdk: String },
+    #[serde(rename = "satori/sdk-disconnected")]
+    SdkDisconnected { sdk: String },
+    /// A bot became reachable through at least one SDK.
+    #[serde(rename = "satori/bot-login")]
+    BotLogin { bot: BotId },
+    /// A bot is no longer reachable through any SDK.
+    #[serde(rename = "satori/bot-logout")]
+    BotLogout { bot: BotId },
+    #[serde(rename = "satori/app-started")]
+    AppStarted { app: String },
+    /// Sent once per app after [`ShutdownRequested`](Self::ShutdownRequested);
+    /// its handlers are waited for before SDKs disconnect.
+    #[serde(rename = "satori/app-stopping")]
+    AppStopping { app: String },
+    #[serde(rename = "satori/shutdown-requested")]
+    ShutdownRequested,
+    #[serde(rename = "satori/app-error")]
+    AppError {
+        app: String,
+        event_id: i64,
+        event_type: String,
+        platform: String,
+        self_id: String,
+        channel_id: Option<String>,
+        error: String,
+    },
+}
+
+impl SystemEvent {
+    pub fn into_event(self) -> Event {
+        let (platform, self_id) = match &self {
+            Self::BotLogin { bot } | Self::BotLogout { bot } => {
+                (bot.platform.clone(), bot.id.clone())
+            }
+            _ => (SATORI.to_string(), String::new()),
+        };
+        let value = serde_json::to_value(self).unwrap();
+        Event {
+            ty: INTERNAL.to_string(),
+            platform,
+            self_id,
+            internal_type: value["_type"].as_str().map(ToString::to_string),
+            internal_data: Some(value.get("_data").cloned().unwrap_or(json!({}))),
+            ..Default::default()
+        }
+    }
+
+    /// Parse a system event, `None` for platform and unknown internal events.
+    pub fn from_event(event: &Event) -> Option<Self> {
+        if event.ty != INTERNAL {
+            return None;
+        }
+        let value = match &event.internal_data {
+            Some(data) if !data.as_object().is_some_and(|o| o.is_empty()) => {
+                json!({ "_type": event.internal_type, "_data": data })
+            }
+            _ => json!({ "_type": event.internal_type }),
+        };
+        serde_json::from_value(value).ok()
+    }
+}
+
+/// Whether `event` was emitted by Satori itself rather than a platform.
+pub fn is_internal(event: &Event) -> bool {
+    event.ty == INTERNAL
+}
+
+/// Deliver events queued with [`Runtime::emit`](crate::runtime::Runtime::emit)
+/// until the runtime stops, and announce `apps` as started.
+#[doc(hidden)]
+pub fn started<S>(s: &Arc<S>, apps: Vec<&'static str>)
+where
+    S: Satori + Send + Sync + 'static,
+{
+    for app in apps {
+        s.runtime().emit(SystemEvent::AppStarted {
+            app: app.to_string(),
+        });
+    }
+    let Some(mut rx) = s.runtime().take_system_events() else {
+        return;
+    };
+    let s = s.clone();
+    tokio::spawn(async move {
+        loop {
+            tokio::select! {
+                Some(event) = rx.recv() => s.handle_event(event.into_event()).await,
+                _ = s.stopped() => break,
+            }
+        }
+    });
+}
+
+/// Tell `apps` that shutdown was requested, returning once dispatched.
+#[doc(hidden)]
+pub async fn stopping<S>(s: &Arc<S>, apps: Vec<&'static str>)
+where
+    S: Satori + Send + Sync + 'static,
+{
+    s.handle_event(SystemEvent::ShutdownRequested.into_event())
+        .await;
+    for app in apps {
+        let event = SystemEvent::AppStopping {
+            app: app.to_string(),
+        };
+        s.handle_event(event.into_event()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SystemEvent;
+    use crate::structs::BotId;
+
+    #[test]
+    fn test_roundtrip() {
+        for event in [
+            SystemEvent::ShutdownRequested,
+            SystemEvent::BotLogin {
+                bot: BotId {
+                    id: "1".to_string(),
+                    platform: "test".to_string(),
+                },
+            },
+            SystemEvent::AppStarted {
+                app: "echo".to_string(),
+            },
+        ] {
+            let raw = event.clone().into_event();
+            assert_eq!(SystemEvent::from_event(&raw), Some(event));
+        }
+    }
+}
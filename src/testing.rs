@@ -0,0 +1,81 @@
+//! Helpers shared by the unit tests.
+
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+
+use crate::{
+    api::RawApiCall,
+    error::SatoriError,
+    structs::{BotId, Channel, Event, Login, Message, User},
+    Satori, SatoriSDK,
+};
+
+/// An SDK serving every bot that records the content of each call.
+pub struct Sink(pub Arc<Mutex<Vec<Value>>>);
+
+impl SatoriSDK for Sink {
+    async fn start<S>(&self, _s: &Arc<S>)
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+    }
+
+    async fn call_api<S>(
+        &self,
+        _s: &Arc<S>,
+        _bot: &BotId,
+        payload: RawApiCall,
+    ) -> Result<Value, SatoriError>
+    where
+        S: Satori + Send + Sync + 'static,
+    {
+        self.0.lock().unwrap().push(payload.body["content"].clone());
+        Ok(json!({}))
+    }
+
+    async fn has_bot(&self, _bot: &BotId) -> bool {
+        true
+    }
+
+    async fn get_logins(&self) -> Vec<Login> {
+        vec![]
+    }
+}
+
+pub fn bot() -> BotId {
+    BotId {
+        id: "1".to_string(),
+        platform: "test".to_string(),
+    }
+}
+
+/// A message from `user` in channel `c`.
+pub fn message(user: &str, content: &str) -> Event {
+    Event {
+        ty: "message-created".to_string(),
+        channel: Some(Channel {
+            id: "c".to_string(),
+            ..Default::default()
+        }),
+        user: Some(User {
+            id: user.to_string(),
+            ..Default::default()
+        }),
+        message: Some(Message {
+            content: Some(content.to_string()),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+/// A local port nothing listens on.
+#[cfg(all(feature = "net-app", feature = "net-sdk"))]
+pub fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
@@ -0,0 +1,186 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+};
+
+use serde_json::Value;
+
+use crate::{
+    error::{MapSatoriError, SatoriError},
+    structs::BotId,
+};
+
+/// A file for `upload.create`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Upload {
+    pub data: Vec<u8>,
+    pub mime: Option<String>,
+    pub filename: Option<String>,
+}
+
+impl Upload {
+    pub fn new(data: impl Into<Vec<u8>>) -> Self {
+        Self {
+            data: data.into(),
+            ..Default::default()
+        }
+    }
+
+    pub fn mime(mut self, mime: impl Into<String>) -> Self {
+        self.mime = Some(mime.into());
+        self
+    }
+
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+}
+
+/// The URL an upload passed through API middlewares resulted in.
+#[doc(hidden)]
+pub fn into_url(value: Value) -> Result<String, SatoriError> {
+    match value {
+        Value::String(url) => Ok(url),
+        value => Err(anyhow::anyhow!("upload answered {value}").into()),
+    }
+}
+
+/// Default in-memory limit of an [`UploadStore`], in bytes.
+pub const DEFAULT_MEMORY_BYTES: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, Default)]
+struct Files {
+    files: HashMap<String, Upload>,
+    /// URLs, oldest first.
+    order: VecDeque<String>,
+    bytes: usize,
+}
+
+impl Files {
+    fn remove(&mut self, url: &str) -> Option<Upload> {
+        let file = self.files.remove(url)?;
+        self.order.retain(|u| u != url);
+        self.bytes -= file.data.len();
+        Some(file)
+    }
+}
+
+/// Keeps uploads for SDKs that can't upload themselves.
+///
+/// Without a directory, files stay in memory under
+/// `internal:{platform}/{self_id}/upload/{id}` URLs, which
+/// [`NetApp`](crate::impls::net::app::NetApp) serves on its proxy route.
+/// Once they take more than `memory_bytes`, the oldest ones are dropped.
+/// With a directory, files are written there and get `file://` URLs.
+#[derive(Debug)]
+pub struct UploadStore {
+    dir: Option<PathBuf>,
+    memory_bytes: usize,
+    next: AtomicU64,
+    files: Mutex<Files>,
+}
+
+impl Default for UploadStore {
+    fn default() -> Self {
+        Self::new(None, DEFAULT_MEMORY_BYTES)
+    }
+}
+
+impl UploadStore {
+    pub fn new(dir: Option<PathBuf>, memory_bytes: usize) -> Self {
+        Self {
+            dir,
+            memory_bytes,
+            next: AtomicU64::new(0),
+            files: Default::default(),
+        }
+    }
+
+    /// Store `file`, returning its URL.
+    pub async fn put(&self, bot: &BotId, file: Upload) -> Result<String, SatoriError> {
+        let id = self.next.fetch_add(1, Ordering::Relaxed);
+        let Some(dir) = &self.dir else {
+            let size = file.data.len();
+            if size > self.memory_bytes {
+                return Err(anyhow::anyhow!("upload of {size} bytes too large to keep").into());
+            }
+            let url = format!("internal:{}/{}/upload/{id}", bot.platform, bot.id);
+            let mut files = self.files.lock().unwrap();
+            while files.bytes + size > self.memory_bytes {
+                let Some(oldest) = files.order.front().cloned() else {
+                    break;
+                };
+                files.remove(&oldest);
+            }
+            files.bytes += size;
+            files.order.push_back(url.clone());
+            files.files.insert(url.clone(), file);
+            return Ok(url);
+        };
+        // keep the extension, platforms often sniff types by it
+        let name = match file.filename.as_deref().and_then(|f| f.rsplit_once('.')) {
+            Some((_, ext)) => format!("{}-{}-{id}.{ext}", bot.platform, bot.id),
+            None => format!("{}-{}-{id}", bot.platform, bot.id),
+        };
+        tokio::fs::create_dir_all(dir).await.map_internal_error()?;
+        let path = std::path::absolute(dir.join(name)).map_internal_error()?;
+        tokio::fs::write(&path, &file.data)
+            .await
+            .map_internal_error()?;
+        Ok(format!("file://{}", path.display()))
+    }
+
+    /// A file stored in memory.
+    pub fn get(&self, url: &str) -> Option<Upload> {
+        self.files.lock().unwrap().files.get(url).cloned()
+    }
+
+    pub fn remove(&self, url: &str) -> Option<Upload> {
+        self.files.lock().unwrap().remove(url)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Upload, UploadStore};
+    use crate::structs::BotId;
+
+    #[tokio::test]
+    async fn test_store() {
+        let bot = BotId {
+            id: "1".to_string(),
+            platform: "test".to_string(),
+        };
+        let memory = UploadStore::default();
+        let url = memory.put(&bot, Upload::new("hi")).await.unwrap();
+        assert_eq!(url, "internal:test/1/upload/0");
+        assert_eq!(memory.get(&url), Some(Upload::new("hi")));
+
+        // the oldest files make room for new ones
+        let small = UploadStore::new(None, 4);
+        let a = small.put(&bot, Upload::new("ab")).await.unwrap();
+        let b = small.put(&bot, Upload::new("cd")).await.unwrap();
+        let c = small.put(&bot, Upload::new("e")).await.unwrap();
+        assert_eq!(small.get(&a), None);
+        assert_eq!(small.get(&b), Some(Upload::new("cd")));
+        assert_eq!(small.get(&c), Some(Upload::new("e")));
+        assert!(small.put(&bot, Upload::new("fghij")).await.is_err());
+        assert_eq!(small.remove(&b), Some(Upload::new("cd")));
+        small.put(&bot, Upload::new("klm")).await.unwrap();
+        assert_eq!(small.get(&c), Some(Upload::new("e")));
+
+        let dir = std::env::temp_dir().join(format!("satori-upload-{}", std::process::id()));
+        let disk = UploadStore::new(Some(dir.clone()), 0);
+        let file = Upload::new("png").filename("a.png");
+        let url = disk.put(&bot, file).await.unwrap();
+        let path = url.strip_prefix("file://").unwrap();
+        assert!(path.ends_with("test-1-0.png"));
+        assert_eq!(std::fs::read(path).unwrap(), b"png");
+        std::fs::remove_dir_all(dir).ok();
+    }
+}